@@ -0,0 +1,42 @@
+// access_log.rs
+//
+// A terse access log, one line per scrape on stdout, for when the JSON
+// tracing output is too verbose to grep. Enabled by ACCESS_LOG. Each line is
+// `access` followed by space-separated `key=value` pairs, with `-` for a
+// value that isn't known:
+//
+//   access method=GET host=example.com status=200 bytes=5120 ms=184 proxy=false
+use std::io::Write;
+use std::time::Duration;
+
+/// What the access log records about one scrape.
+pub struct AccessLogEntry<'a> {
+    pub method: &'a str,
+    // Host of the target URL, if it parsed
+    pub host: Option<&'a str>,
+    // Status we answered our caller with
+    pub status: u16,
+    // Size of the target's body, if one was received
+    pub bytes: Option<usize>,
+    pub elapsed: Duration,
+    // Whether the request went through a proxy
+    pub proxy: bool,
+}
+
+impl AccessLogEntry<'_> {
+    /// Writes the entry as a single line. A closed or full stdout is ignored
+    /// rather than failing the scrape.
+    pub fn write(&self) {
+        let bytes = self.bytes.map_or_else(|| "-".to_string(), |bytes| bytes.to_string());
+        let line = format!(
+            "access method={} host={} status={} bytes={} ms={} proxy={}\n",
+            self.method,
+            self.host.unwrap_or("-"),
+            self.status,
+            bytes,
+            self.elapsed.as_millis(),
+            self.proxy,
+        );
+        let _ = std::io::stdout().lock().write_all(line.as_bytes());
+    }
+}
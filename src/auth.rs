@@ -0,0 +1,60 @@
+// auth.rs
+//
+// Bearer-token authentication for the scrape endpoints. When API_TOKEN is
+// set, requests must send `Authorization: Bearer <API_TOKEN>`; when it's
+// unset the endpoints stay open, which is convenient for local development.
+use crate::config::Config;
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use tracing::warn;
+
+#[derive(Serialize)]
+struct UnauthorizedResponse {
+    error: &'static str,
+}
+
+/// Middleware rejecting requests whose bearer token doesn't match `API_TOKEN`
+/// with a 401.
+pub async fn require_bearer_token(
+    req: ServiceRequest,
+    next: Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let expected = req
+        .app_data::<web::Data<Config>>()
+        .and_then(|config| config.api_token.clone());
+    let Some(expected) = expected else {
+        return next.call(req).await;
+    };
+
+    let presented = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            let (scheme, token) = value.trim().split_once(' ')?;
+            scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+        });
+
+    let error = match presented {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+            return next.call(req).await;
+        }
+        Some(_) => "Invalid bearer token",
+        None => "Missing bearer token",
+    };
+    warn!(path = req.path(), error, "Rejecting unauthenticated request");
+    let response = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, "Bearer"))
+        .json(UnauthorizedResponse { error });
+    Ok(req.into_response(response))
+}
+
+/// Compares two byte strings in time that depends only on their lengths, so
+/// the token can't be guessed byte by byte from response timings.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,324 @@
+// batch_api.rs
+//
+// POST /batch and /batch/stream: several URLs scraped through one client,
+// their results returned together in request order or streamed as Server-Sent
+// Events as each finishes. Fetches hold a permit from the shared semaphore, so
+// at most MAX_CONCURRENCY batch fetches run at once across the process.
+use crate::{
+    check_robots, error_body, fetch, normalize_url, request_id, select_client, with_hard_timeout, with_request_id,
+    ClientOptions, FetchOptions, ProxyFields, ScrapeError, SelectedClient,
+};
+use crate::circuit_breaker::CircuitBreakers;
+use crate::config::Config;
+use crate::politeness::HostThrottle;
+use crate::proxy_pool::ProxyPool;
+use crate::robots::RobotsCache;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures::{future, stream, StreamExt};
+use reqwest::Client;
+use reqwest::header::CACHE_CONTROL;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{field, info, info_span, Instrument, Span};
+
+// Define the structure for the incoming batch POST request
+#[derive(Deserialize)]
+pub struct BatchScrapeRequest {
+    pub urls: Vec<String>,
+    // Optional proxy and timeouts, the timeouts applying to each URL individually
+    #[serde(flatten)]
+    pub proxy_fields: ProxyFields,
+    // Optional robots.txt check, as in `ScrapeRequest`, applied to each URL
+    pub respect_robots: Option<bool>,
+    // Optional cap on the retries made across all the URLs together; once
+    // it's spent, failures are returned without retrying
+    pub retry_budget: Option<u32>,
+}
+
+// Outcome of scraping a single URL within a batch
+#[derive(Serialize, Clone)]
+pub struct ScrapeResult {
+    pub url: String,
+    // Upstream HTTP status, absent if no response was received
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    // The base64-encoded body of a non-text response, replacing `content`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_base64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Machine-readable class of `error`, as in `ScrapeResponse`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<&'static str>,
+}
+
+// Define the structure for the outgoing batch JSON response.
+// Results are returned in the same order as the requested URLs.
+#[derive(Serialize)]
+struct BatchScrapeResponse {
+    results: Vec<ScrapeResult>,
+    // Retries taken from the request's `retry_budget`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_budget_used: Option<u32>,
+}
+
+/// Handles the POST request to scrape several URLs at once.
+///
+/// The URLs are fetched concurrently using the same proxy and timeout rules as
+/// `scrape_handler`. Each fetch holds a permit from the shared semaphore, so at
+/// most `MAX_CONCURRENCY` batch fetches are in flight across the whole process.
+/// Each URL gets its own result, so a failure on one doesn't affect the others.
+pub async fn batch_scrape_handler(
+    http_req: HttpRequest,
+    req: web::Json<BatchScrapeRequest>,
+    config: web::Data<Config>,
+    base_client: web::Data<Client>,
+    proxy_pool: web::Data<ProxyPool>,
+    semaphore: web::Data<Semaphore>,
+    robots_cache: web::Data<RobotsCache>,
+    throttle: web::Data<HostThrottle>,
+    breakers: web::Data<CircuitBreakers>,
+) -> impl Responder {
+    let request_id = request_id(&http_req);
+    let span = info_span!(
+        "batch_scrape",
+        request_id = %request_id,
+        urls = req.urls.len(),
+        proxy = field::Empty,
+    );
+
+    // The client is shared by the whole batch, so a bad proxy fails the batch as a whole
+    let selected = span.in_scope(|| batch_client(&req, &config, &base_client, &proxy_pool, &breakers));
+    let client = match selected {
+        Ok(c) => c,
+        Err(e) => {
+            let response = HttpResponse::build(e.status_code()).json(error_body(&e));
+            return with_request_id(response, &request_id);
+        }
+    };
+
+    span.in_scope(|| info!("Starting batch scrape"));
+
+    let options = FetchOptions::for_batch(&config, &req);
+    let respect_robots = req.respect_robots.unwrap_or(config.respect_robots);
+    let results = future::join_all(req.urls.iter().map(|url| {
+        // Each URL gets its own span, nested under the batch
+        let url_span = info_span!(parent: &span, "scrape", url = %url, status = field::Empty);
+        async {
+            // The permit is released when it goes out of scope at the end of this block
+            let _permit = semaphore.acquire().await.expect("semaphore is never closed");
+            scrape_batch_url(&config, &throttle, &client, &robots_cache, url, &options, respect_robots).await
+        }
+        .instrument(url_span)
+    }))
+    .await;
+
+    let response = BatchScrapeResponse {
+        results,
+        retry_budget_used: options.retry_budget_used(),
+    };
+    with_request_id(HttpResponse::Ok().json(response), &request_id)
+}
+
+/// Picks the client a whole batch is fetched with, from its proxy and timeout settings.
+pub fn batch_client(
+    req: &BatchScrapeRequest,
+    config: &Config,
+    base_client: &Client,
+    proxy_pool: &Arc<ProxyPool>,
+    breakers: &Arc<CircuitBreakers>,
+) -> Result<SelectedClient, ScrapeError> {
+    let client_options = ClientOptions::from_proxy_fields(&req.proxy_fields)?;
+    select_client(config, base_client, proxy_pool, breakers, &client_options)
+}
+
+/// Handles the POST request to scrape a batch as a stream of Server-Sent Events.
+///
+/// Takes the same body as `batch_scrape_handler` and fetches the URLs the same
+/// way, but sends each result as an `event: result` as soon as it's ready, in
+/// completion order, then an `event: done` with the number of results and
+/// any `retry_budget_used`. The fetches belong to the response stream, so a
+/// client that disconnects cancels those not yet finished.
+pub async fn batch_stream_handler(
+    http_req: HttpRequest,
+    req: web::Json<BatchScrapeRequest>,
+    config: web::Data<Config>,
+    base_client: web::Data<Client>,
+    proxy_pool: web::Data<ProxyPool>,
+    semaphore: web::Data<Semaphore>,
+    robots_cache: web::Data<RobotsCache>,
+    throttle: web::Data<HostThrottle>,
+    breakers: web::Data<CircuitBreakers>,
+) -> impl Responder {
+    let request_id = request_id(&http_req);
+    let span = info_span!(
+        "batch_stream",
+        request_id = %request_id,
+        urls = req.urls.len(),
+        proxy = field::Empty,
+    );
+
+    let selected = span.in_scope(|| batch_client(&req, &config, &base_client, &proxy_pool, &breakers));
+    let client = match selected {
+        Ok(client) => Arc::new(client),
+        Err(e) => return with_request_id(HttpResponse::build(e.status_code()).json(error_body(&e)), &request_id),
+    };
+
+    span.in_scope(|| info!("Starting streamed batch scrape"));
+
+    // The stream outlives this handler, so every fetch owns what it uses
+    let req = req.into_inner();
+    let options = Arc::new(FetchOptions::for_batch(&config, &req));
+    let respect_robots = req.respect_robots.unwrap_or(config.respect_robots);
+    let total = req.urls.len();
+    let fetches: stream::FuturesUnordered<_> = req
+        .urls
+        .into_iter()
+        .map(|url| {
+            let url_span = info_span!(parent: &span, "scrape", url = %url, status = field::Empty);
+            let (config, throttle, client, robots_cache, options, semaphore) = (
+                config.clone(),
+                throttle.clone(),
+                client.clone(),
+                robots_cache.clone(),
+                options.clone(),
+                semaphore.clone(),
+            );
+            async move {
+                let _permit = semaphore.acquire().await.expect("semaphore is never closed");
+                scrape_batch_url(&config, &throttle, &client, &robots_cache, &url, &options, respect_robots).await
+            }
+            .instrument(url_span)
+        })
+        .collect();
+
+    let results = fetches.map(|result| sse_event("result", &result));
+    // Built once the results are all sent, so the budget used is final
+    let done = stream::once(async move {
+        let mut done = serde_json::json!({ "results": total });
+        if let Some(used) = options.retry_budget_used() {
+            done["retry_budget_used"] = used.into();
+        }
+        sse_event("done", &done)
+    });
+    let events = results.chain(done).map(Ok::<_, std::convert::Infallible>);
+    let response = HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        .streaming(events);
+    with_request_id(response, &request_id)
+}
+
+/// Formats one Server-Sent Event carrying `data` as JSON, which never spans lines.
+fn sse_event(event: &str, data: &impl Serialize) -> web::Bytes {
+    let data = serde_json::to_string(data).expect("results serialize");
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+/// Scrapes one URL of a batch or job, turning any failure into its result.
+pub async fn scrape_batch_url(
+    config: &Config,
+    throttle: &HostThrottle,
+    client: &SelectedClient,
+    robots_cache: &RobotsCache,
+    url: &str,
+    options: &FetchOptions,
+    respect_robots: bool,
+) -> ScrapeResult {
+    let operation = async {
+        let target = normalize_url(config, url)?;
+        if respect_robots {
+            check_robots(config, throttle, robots_cache, client, &target, options).await?;
+        }
+        fetch(config, throttle, client, &target, options).await.result
+    };
+    let result = with_hard_timeout(config, operation).await;
+    let status = result.as_ref().map_or_else(|e| e.status_code(), |_| StatusCode::OK);
+    Span::current().record("status", status.as_u16());
+    match result {
+        Ok(fetched) => {
+            let (content, content_base64) = match &fetched.binary {
+                Some(binary) => (None, Some(BASE64.encode(binary))),
+                None => (Some(fetched.content), None),
+            };
+            ScrapeResult {
+                url: url.to_string(),
+                status: Some(fetched.meta.status.as_u16()),
+                content,
+                content_base64,
+                error: None,
+                error_code: None,
+            }
+        }
+        Err(e) => ScrapeResult {
+            url: url.to_string(),
+            status: e.response_meta().map(|meta| meta.status.as_u16()),
+            content: None,
+            content_base64: None,
+            error: Some(e.to_string()),
+            error_code: Some(e.code()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{response, serve, serve_after, test_config, TestApp};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn batch_fetches_hold_to_the_semaphore() {
+        let fixture = serve_after(Duration::from_millis(100), |_| response("200 OK", &[], "page")).await;
+        let app = TestApp {
+            semaphore: web::Data::new(Semaphore::new(2)),
+            ..TestApp::new()
+        };
+        let urls: Vec<String> = (0..6).map(|page| format!("{}/{}", fixture.url, page)).collect();
+        let (status, body) = app.batch(serde_json::json!({ "urls": urls })).await;
+        assert_eq!(status, StatusCode::OK);
+        let statuses: Vec<_> = body["results"].as_array().expect("results").iter().map(|r| &r["status"]).collect();
+        assert_eq!(statuses, [200; 6]);
+        assert_eq!(fixture.peak_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_web::test]
+    async fn batch_proxy_fields_fail_the_batch_as_a_whole() {
+        let request = serde_json::json!({
+            "urls": ["http://example.test/"],
+            "proxy": "socks5h://127.0.0.1:9050",
+            "proxy_type": "ftp",
+        });
+        let (status, body) = TestApp::new().batch(request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error_code"], "invalid_proxy");
+        assert!(body.get("results").is_none());
+    }
+
+    #[actix_web::test]
+    async fn batch_retries_stop_once_the_budget_is_spent() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+        let fixture = serve(move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            response("503 Service Unavailable", &[], "")
+        })
+        .await;
+        let urls: Vec<String> = (0..4).map(|page| format!("{}/{}", fixture.url, page)).collect();
+        let mut config = test_config();
+        config.max_retries = 3;
+        let app = TestApp::with_config(config);
+        let (status, body) = app.batch(serde_json::json!({ "urls": urls, "retry_budget": 2 })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["retry_budget_used"], 2);
+        // One attempt per URL, plus the two retries the budget allowed
+        assert_eq!(requests.load(Ordering::SeqCst), 6);
+        let statuses: Vec<_> = body["results"].as_array().expect("results").iter().map(|r| &r["status"]).collect();
+        assert_eq!(statuses, [503; 4]);
+    }
+}
@@ -0,0 +1,74 @@
+// browser_profile.rs
+//
+// Header bundles sent with `browser_profile`, so requests carry what a
+// browser would send when navigating to a page rather than the bare
+// User-Agent of an HTTP library. Accept-Encoding is left to the client,
+// which offers what it can decompress. Update the versions here as browsers
+// move on: a stale one stands out as much as none.
+
+/// A set of browser-like request headers.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BrowserProfile {
+    Chrome,
+    Firefox,
+    // Nothing beyond DEFAULT_USER_AGENT and DEFAULT_ACCEPT_LANGUAGE
+    None,
+}
+
+// Chrome on Windows opening a page typed into the address bar
+const CHROME_HEADERS: &[(&str, &str)] = &[
+    (
+        "user-agent",
+        "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Safari/537.36",
+    ),
+    (
+        "accept",
+        "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,\
+         application/signed-exchange;v=b3;q=0.7",
+    ),
+    ("accept-language", "en-US,en;q=0.9"),
+    ("sec-ch-ua", "\"Google Chrome\";v=\"129\", \"Not=A?Brand\";v=\"8\", \"Chromium\";v=\"129\""),
+    ("sec-ch-ua-mobile", "?0"),
+    ("sec-ch-ua-platform", "\"Windows\""),
+    ("upgrade-insecure-requests", "1"),
+    ("sec-fetch-site", "none"),
+    ("sec-fetch-mode", "navigate"),
+    ("sec-fetch-user", "?1"),
+    ("sec-fetch-dest", "document"),
+];
+
+// Firefox on Windows, likewise; it sends no client hints
+const FIREFOX_HEADERS: &[(&str, &str)] = &[
+    ("user-agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:131.0) Gecko/20100101 Firefox/131.0"),
+    (
+        "accept",
+        "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/png,image/svg+xml,*/*;q=0.8",
+    ),
+    ("accept-language", "en-US,en;q=0.5"),
+    ("upgrade-insecure-requests", "1"),
+    ("sec-fetch-dest", "document"),
+    ("sec-fetch-mode", "navigate"),
+    ("sec-fetch-site", "none"),
+    ("sec-fetch-user", "?1"),
+];
+
+impl BrowserProfile {
+    /// Parses a profile name, case-insensitively.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "chrome" => Some(BrowserProfile::Chrome),
+            "firefox" => Some(BrowserProfile::Firefox),
+            "none" => Some(BrowserProfile::None),
+            _ => None,
+        }
+    }
+
+    /// The profile's headers, with lowercase names, in the order the browser sends them.
+    pub fn headers(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            BrowserProfile::Chrome => CHROME_HEADERS,
+            BrowserProfile::Firefox => FIREFOX_HEADERS,
+            BrowserProfile::None => &[],
+        }
+    }
+}
@@ -0,0 +1,178 @@
+// circuit_breaker.rs
+//
+// Per-proxy circuit breakers. After CIRCUIT_BREAKER_THRESHOLD consecutive
+// requests through a proxy fail to connect or time out, its circuit opens and
+// requests routed to it are refused straight away instead of each waiting out
+// the timeout. Once CIRCUIT_BREAKER_COOLDOWN_SECONDS have passed the circuit
+// is half-open: a single request is let through, and its outcome closes the
+// circuit again or reopens it for another cooldown.
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// What a breaker currently does with requests.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CircuitState {
+    // Requests go through
+    Closed,
+    // Requests are refused until the cooldown is over
+    Open,
+    // One request is probing whether the proxy has recovered
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// The value reported for this state in `/metrics`.
+    pub fn gauge_value(self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::Open => 1,
+            CircuitState::HalfOpen => 2,
+        }
+    }
+}
+
+struct Breaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    // When the circuit opened, or when the current probe started
+    since: Instant,
+}
+
+/// Breakers for every proxy that has been used, shared through `web::Data`.
+pub struct CircuitBreakers {
+    // Consecutive failures that open a circuit; 0 disables the breakers
+    threshold: u32,
+    cooldown: Duration,
+    breakers: Mutex<HashMap<String, Breaker>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreakers {
+            threshold,
+            cooldown,
+            breakers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a request may go through the proxy at `addr`. An open circuit
+    /// whose cooldown is over turns half-open and lets this one request
+    /// through; should that probe never report back, another one is let
+    /// through after a further cooldown.
+    pub fn allow(&self, addr: &str) -> bool {
+        let mut breakers = self.breakers.lock().expect("circuit breaker lock poisoned");
+        let Some(breaker) = breakers.get_mut(addr) else {
+            return true;
+        };
+        match breaker.state {
+            CircuitState::Closed => true,
+            CircuitState::Open | CircuitState::HalfOpen if breaker.since.elapsed() >= self.cooldown => {
+                breaker.state = CircuitState::HalfOpen;
+                breaker.since = Instant::now();
+                true
+            }
+            CircuitState::Open | CircuitState::HalfOpen => false,
+        }
+    }
+
+    /// Records whether a request through the proxy at `addr` reached the
+    /// proxy and got an answer; failures count towards opening its circuit.
+    pub fn record(&self, addr: &str, success: bool) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut breakers = self.breakers.lock().expect("circuit breaker lock poisoned");
+        // Proxies that never failed don't need a breaker yet
+        if success && !breakers.contains_key(addr) {
+            return;
+        }
+        let breaker = breakers.entry(addr.to_string()).or_insert(Breaker {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            since: Instant::now(),
+        });
+        if success {
+            breaker.state = CircuitState::Closed;
+            breaker.consecutive_failures = 0;
+        } else {
+            breaker.consecutive_failures += 1;
+            if breaker.state == CircuitState::HalfOpen || breaker.consecutive_failures >= self.threshold {
+                breaker.state = CircuitState::Open;
+                breaker.since = Instant::now();
+            }
+        }
+    }
+
+    /// The state of the circuit of every proxy that has failed at some point.
+    pub fn states(&self) -> Vec<(String, CircuitState)> {
+        let breakers = self.breakers.lock().expect("circuit breaker lock poisoned");
+        breakers
+            .iter()
+            .map(|(addr, breaker)| (addr.clone(), breaker.state))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(breakers: &CircuitBreakers, addr: &str) -> Option<CircuitState> {
+        breakers.states().into_iter().find(|(proxy, _)| proxy == addr).map(|(_, state)| state)
+    }
+
+    #[test]
+    fn consecutive_failures_open_the_circuit() {
+        let breakers = CircuitBreakers::new(3, Duration::from_secs(60));
+        breakers.record("a", true);
+        assert_eq!(state(&breakers, "a"), None);
+        breakers.record("a", false);
+        breakers.record("a", false);
+        // A success in between starts the count again
+        breakers.record("a", true);
+        breakers.record("a", false);
+        breakers.record("a", false);
+        assert_eq!(state(&breakers, "a"), Some(CircuitState::Closed));
+        assert!(breakers.allow("a"));
+        breakers.record("a", false);
+        assert_eq!(state(&breakers, "a"), Some(CircuitState::Open));
+        assert!(!breakers.allow("a"));
+        assert!(breakers.allow("b"));
+    }
+
+    #[test]
+    fn half_open_probe_closes_or_reopens_the_circuit() {
+        let breakers = CircuitBreakers::new(1, Duration::ZERO);
+        breakers.record("a", false);
+        assert_eq!(state(&breakers, "a"), Some(CircuitState::Open));
+        // The cooldown is over at once, so the next request probes
+        assert!(breakers.allow("a"));
+        assert_eq!(state(&breakers, "a"), Some(CircuitState::HalfOpen));
+        breakers.record("a", false);
+        assert_eq!(state(&breakers, "a"), Some(CircuitState::Open));
+        assert!(breakers.allow("a"));
+        breakers.record("a", true);
+        assert_eq!(state(&breakers, "a"), Some(CircuitState::Closed));
+    }
+
+    #[test]
+    fn probe_holds_off_other_requests_during_the_cooldown() {
+        let breakers = CircuitBreakers::new(1, Duration::from_millis(50));
+        breakers.record("a", false);
+        assert!(!breakers.allow("a"));
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breakers.allow("a"));
+        assert!(!breakers.allow("a"));
+    }
+
+    #[test]
+    fn zero_threshold_disables_the_breakers() {
+        let breakers = CircuitBreakers::new(0, Duration::from_secs(60));
+        for _ in 0..10 {
+            breakers.record("a", false);
+        }
+        assert!(breakers.allow("a"));
+        assert!(breakers.states().is_empty());
+    }
+}
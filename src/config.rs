@@ -0,0 +1,560 @@
+// config.rs
+use crate::browser_profile::BrowserProfile;
+use crate::dns;
+use crate::extract;
+use crate::proxy_chain::ProxyChain;
+use crate::proxy_pool::{PoolProxy, ProxyPool};
+use crate::redact;
+use crate::ssrf::SsrfGuard;
+use hickory_resolver::TokioAsyncResolver;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Certificate, Identity, Proxy};
+use std::env;
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Listening address when BIND_HOST is unset; all interfaces, so the service is
+// reachable from outside the container in a Kubernetes environment
+const DEFAULT_BIND_HOST: &str = "0.0.0.0";
+// Listening port when BIND_PORT is unset, consistent with the Containerfile
+const DEFAULT_BIND_PORT: u16 = 8282;
+// Timeout applied when the request doesn't specify one and DEFAULT_TIMEOUT_SECONDS is unset
+const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+// Number of batch fetches allowed in flight when MAX_CONCURRENCY is unset
+const DEFAULT_MAX_CONCURRENCY: usize = 8;
+// Scraping requests handled at once when MAX_IN_FLIGHT_REQUESTS is unset
+const DEFAULT_MAX_IN_FLIGHT_REQUESTS: usize = 256;
+// Longest URL accepted when MAX_URL_LENGTH is unset
+const DEFAULT_MAX_URL_LENGTH: usize = 8192;
+// Upper bound on retries, whatever the request or MAX_RETRIES asks for
+pub const MAX_RETRIES_LIMIT: u32 = 10;
+// Longest Retry-After we'll honour when MAX_RETRY_AFTER_SECONDS is unset
+const DEFAULT_MAX_RETRY_AFTER_SECONDS: u64 = 60;
+// How long a fetched robots.txt is trusted when ROBOTS_CACHE_TTL_SECONDS is unset
+const DEFAULT_ROBOTS_CACHE_TTL_SECONDS: u64 = 3600;
+// Responses kept in the cache when RESPONSE_CACHE_MAX_ENTRIES is unset
+const DEFAULT_RESPONSE_CACHE_MAX_ENTRIES: usize = 1000;
+// Requests per minute allowed for each API key when RATE_LIMIT_PER_MINUTE is unset
+const DEFAULT_RATE_LIMIT_PER_MINUTE: u32 = 60;
+// Jobs processed at once when JOB_WORKERS is unset
+const DEFAULT_JOB_WORKERS: usize = 2;
+// How long a finished job's results are kept when JOB_TTL_SECONDS is unset
+const DEFAULT_JOB_TTL_SECONDS: u64 = 3600;
+// Consecutive failures opening a proxy's circuit when CIRCUIT_BREAKER_THRESHOLD is unset
+const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
+// How long an open circuit refuses requests when CIRCUIT_BREAKER_COOLDOWN_SECONDS is unset
+const DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECONDS: u64 = 30;
+// How long an idle cookie session is kept when SESSION_TTL_SECONDS is unset
+const DEFAULT_SESSION_TTL_SECONDS: u64 = 3600;
+// Grace period for in-flight requests when SHUTDOWN_TIMEOUT_SECONDS is unset
+const DEFAULT_SHUTDOWN_TIMEOUT_SECONDS: u64 = 30;
+// Idle connections kept per host when POOL_MAX_IDLE_PER_HOST is unset; enough
+// to keep a busy host warm without hoarding sockets
+const DEFAULT_POOL_MAX_IDLE_PER_HOST: usize = 32;
+// How long an idle connection is kept when POOL_IDLE_TIMEOUT_SECONDS is unset, as in reqwest
+const DEFAULT_POOL_IDLE_TIMEOUT_SECONDS: u64 = 90;
+// Interval of TCP keepalive probes when TCP_KEEPALIVE_SECONDS is unset
+const DEFAULT_TCP_KEEPALIVE_SECONDS: u64 = 60;
+// Most headers accepted in a response when MAX_RESPONSE_HEADERS is unset, as
+// many as hyper parses in an HTTP/1 response anyway
+const DEFAULT_MAX_RESPONSE_HEADERS: usize = 100;
+// Largest header section accepted in a response when MAX_RESPONSE_HEADER_BYTES is unset
+const DEFAULT_MAX_RESPONSE_HEADER_BYTES: usize = 64 * 1024;
+// IP-echo service answering with the caller's public IP as plain text, when EXIT_IP_URL is unset
+const DEFAULT_EXIT_IP_URL: &str = "https://api.ipify.org";
+// How long a proxy's exit IP is trusted when EXIT_IP_CACHE_TTL_SECONDS is unset;
+// short, as Tor moves to a new circuit every ten minutes or so
+const DEFAULT_EXIT_IP_CACHE_TTL_SECONDS: u64 = 60;
+
+/// A configuration value that couldn't be used.
+#[derive(Debug)]
+pub struct ConfigError(String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl StdError for ConfigError {}
+
+/// Service configuration, read from the environment once at startup and
+/// shared with the handlers through `web::Data`.
+pub struct Config {
+    // Address and port the server listens on, from BIND_HOST and BIND_PORT
+    pub host: String,
+    pub port: u16,
+    // Proxy forced onto every request, from DEFAULT_SOCKS5_PROXY
+    pub default_proxy: Option<String>,
+    // User-Agent sent when the request doesn't set one, from DEFAULT_USER_AGENT
+    pub user_agent: Option<String>,
+    // Accept-Language sent when the request doesn't set one, from DEFAULT_ACCEPT_LANGUAGE
+    pub accept_language: Option<HeaderValue>,
+    // Browser headers sent when the request doesn't pick a profile, from DEFAULT_BROWSER_PROFILE
+    pub browser_profile: BrowserProfile,
+    // Proxies rotated through when no other proxy applies, with their weights, from PROXY_POOL
+    pub proxy_pool: Vec<PoolProxy>,
+    // HTTP proxy every configured or requested proxy is reached through, from UPSTREAM_PROXY
+    pub proxy_chain: Option<ProxyChain>,
+    // Overall request timeout when the request doesn't set one, from DEFAULT_TIMEOUT_SECONDS
+    pub timeout_seconds: u64,
+    // Connection timeout when the request doesn't set one, from DEFAULT_CONNECT_TIMEOUT_SECONDS
+    pub connect_timeout_seconds: Option<u64>,
+    // Longest wait for the next part of a body when the request doesn't set one,
+    // from DEFAULT_READ_TIMEOUT_SECONDS
+    pub read_timeout_seconds: Option<u64>,
+    // Retries when the request doesn't set `max_retries`, from MAX_RETRIES
+    pub max_retries: u32,
+    // Largest response body accepted, from MAX_RESPONSE_BYTES; unlimited when `None`
+    pub max_response_bytes: Option<usize>,
+    // Content-Types a response may have, or any when empty, from ALLOWED_CONTENT_TYPES
+    pub allowed_content_types: Vec<String>,
+    // Most headers accepted in a response, from MAX_RESPONSE_HEADERS
+    pub max_response_headers: usize,
+    // Largest header section accepted in a response, from MAX_RESPONSE_HEADER_BYTES
+    pub max_response_header_bytes: usize,
+    // Longest Retry-After delay honoured, from MAX_RETRY_AFTER_SECONDS
+    pub max_retry_after: Duration,
+    // Batch fetches allowed in flight across the process, from MAX_CONCURRENCY
+    pub max_concurrency: usize,
+    // Scraping requests handled at once across the process, from
+    // MAX_IN_FLIGHT_REQUESTS; unlimited when `None`, which 0 asks for
+    pub max_in_flight_requests: Option<usize>,
+    // Longest URL accepted to scrape, in bytes once normalized, from MAX_URL_LENGTH
+    pub max_url_length: usize,
+    // Grace period for in-flight requests on shutdown, from SHUTDOWN_TIMEOUT_SECONDS
+    pub shutdown_timeout: Duration,
+    // Which targets may be scraped, from ALLOW_PRIVATE_IPS and BLOCKED_HOSTS
+    pub ssrf_guard: Arc<SsrfGuard>,
+    // Headers masked in logs: the built-in set plus any from SENSITIVE_HEADERS
+    pub sensitive_headers: Vec<HeaderName>,
+    // Upstream headers left out of the headers returned to callers, from STRIP_RESPONSE_HEADERS
+    pub strip_response_headers: Vec<HeaderName>,
+    // Whether robots.txt is honoured when the request doesn't say, from RESPECT_ROBOTS
+    pub respect_robots: bool,
+    // How long a fetched robots.txt is cached per host, from ROBOTS_CACHE_TTL_SECONDS
+    pub robots_cache_ttl: Duration,
+    // Most responses kept in the response cache, from RESPONSE_CACHE_MAX_ENTRIES; 0 disables it
+    pub response_cache_max_entries: usize,
+    // Bearer token the scrape endpoints require, from API_TOKEN; open when unset
+    pub api_token: Option<String>,
+    // Keys accepted in X-Api-Key, from API_KEYS; when empty no key is required
+    pub api_keys: Vec<String>,
+    // Requests per minute, and burst size, allowed for each key, from RATE_LIMIT_PER_MINUTE
+    pub rate_limit_per_minute: u32,
+    // Background workers taking jobs from the queue, from JOB_WORKERS
+    pub job_workers: usize,
+    // How long a finished job can still be polled, from JOB_TTL_SECONDS
+    pub job_ttl: Duration,
+    // Least time between two requests to the same host, from PER_HOST_DELAY_MS; zero disables it
+    pub per_host_delay: Duration,
+    // Consecutive failures that open a proxy's circuit, from CIRCUIT_BREAKER_THRESHOLD; 0 disables it
+    pub circuit_breaker_threshold: u32,
+    // How long an open circuit refuses requests, from CIRCUIT_BREAKER_COOLDOWN_SECONDS
+    pub circuit_breaker_cooldown: Duration,
+    // How long a cookie session is kept after its last use, from SESSION_TTL_SECONDS
+    pub session_ttl: Duration,
+    // Whether requests may turn off certificate verification, from ALLOW_INSECURE_TLS
+    pub allow_insecure_tls: bool,
+    // Root certificates trusted on top of the built-in ones, from the PEM bundle at TLS_CA_BUNDLE
+    pub ca_certificates: Vec<Certificate>,
+    // Client certificate presented for mutual TLS, from TLS_CLIENT_CERT and TLS_CLIENT_KEY
+    pub client_identity: Option<Identity>,
+    // Resolver for direct connections, from DNS_RESOLVER; the system one when `None`
+    pub dns_resolver: Option<Arc<TokioAsyncResolver>>,
+    // Ceiling on a whole outbound operation, retries included, from HARD_TIMEOUT_SECONDS
+    pub hard_timeout: Option<Duration>,
+    // Whether each scrape gets a one-line access log entry on stdout, from ACCESS_LOG
+    pub access_log: bool,
+    // Whether JSON responses are compressed for callers that accept it, from COMPRESS_RESPONSES
+    pub compress_responses: bool,
+    // Idle connections each client keeps per host, from POOL_MAX_IDLE_PER_HOST; 0 disables pooling
+    pub pool_max_idle_per_host: usize,
+    // How long an idle pooled connection is kept, from POOL_IDLE_TIMEOUT_SECONDS
+    pub pool_idle_timeout: Duration,
+    // Interval of TCP keepalive probes, from TCP_KEEPALIVE_SECONDS; `None` when 0 turns them off
+    pub tcp_keepalive: Option<Duration>,
+    // Tor control port asked for new circuits, from TOR_CONTROL_ADDR as host:port
+    pub tor_control_addr: Option<String>,
+    // Password for the control port, from TOR_CONTROL_PASSWORD; none when it's left open
+    pub tor_control_password: Option<String>,
+    // IP-echo service asked for a scrape's exit IP, from EXIT_IP_URL
+    pub exit_ip_url: String,
+    // How long an exit IP is reused for the same proxy, from EXIT_IP_CACHE_TTL_SECONDS; 0 disables it
+    pub exit_ip_cache_ttl: Duration,
+}
+
+impl Config {
+    /// Reads and validates the configuration, failing on the first value that
+    /// is malformed rather than falling back to a default.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let default_proxy = env::var("DEFAULT_SOCKS5_PROXY").ok();
+        if let Some(proxy) = &default_proxy {
+            check_proxy("DEFAULT_SOCKS5_PROXY", proxy)?;
+        }
+        let proxy_pool = ProxyPool::parse(&env::var("PROXY_POOL").unwrap_or_default()).map_err(|(addr, weight)| {
+            ConfigError(format!(
+                "Invalid PROXY_POOL: weight of {} must be a positive integer, got '{}'",
+                redact::proxy_url(&addr),
+                weight
+            ))
+        })?;
+        for proxy in &proxy_pool {
+            check_proxy("PROXY_POOL", &proxy.addr)?;
+        }
+        let proxy_chain = env::var("UPSTREAM_PROXY")
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                ProxyChain::parse(&value)
+                    .map_err(|e| ConfigError(format!("Invalid UPSTREAM_PROXY: {} ({})", redact::proxy_url(&value), e)))
+            })
+            .transpose()?;
+        if let Some(chain) = &proxy_chain {
+            let configured = default_proxy.iter().map(|proxy| ("DEFAULT_SOCKS5_PROXY", proxy.as_str()));
+            let pooled = proxy_pool.iter().map(|proxy| ("PROXY_POOL", proxy.addr.as_str()));
+            for (name, proxy) in configured.chain(pooled) {
+                chain.check(proxy).map_err(|e| {
+                    ConfigError(format!("Invalid {}: {} ({})", name, redact::proxy_url(proxy), e))
+                })?;
+            }
+        }
+
+        let host = env::var("BIND_HOST")
+            .map(|host| host.trim().to_string())
+            .unwrap_or_else(|_| DEFAULT_BIND_HOST.to_string());
+        if host.is_empty() {
+            return Err(invalid("BIND_HOST", "a hostname or IP address", ""));
+        }
+        let port = parse_var::<u16>("BIND_PORT", "a port number between 1 and 65535")?
+            .unwrap_or(DEFAULT_BIND_PORT);
+        if port == 0 {
+            return Err(invalid("BIND_PORT", "a port number between 1 and 65535", "0"));
+        }
+
+        let timeout_seconds = parse_var("DEFAULT_TIMEOUT_SECONDS", "a positive number of seconds")?
+            .unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+        if timeout_seconds == 0 {
+            return Err(invalid("DEFAULT_TIMEOUT_SECONDS", "a positive number of seconds", "0"));
+        }
+        // Both are parts of the whole request, so neither can outlast it
+        let mut phase_timeouts = [("DEFAULT_CONNECT_TIMEOUT_SECONDS", None), ("DEFAULT_READ_TIMEOUT_SECONDS", None)];
+        for (name, seconds) in &mut phase_timeouts {
+            *seconds = parse_var::<u64>(name, "a positive number of seconds")?;
+            if *seconds == Some(0) {
+                return Err(invalid(name, "a positive number of seconds", "0"));
+            }
+            if let Some(value) = seconds.filter(|seconds| *seconds > timeout_seconds) {
+                let expected = format!("at most DEFAULT_TIMEOUT_SECONDS ({})", timeout_seconds);
+                return Err(invalid(name, &expected, &value.to_string()));
+            }
+        }
+        let [(_, connect_timeout_seconds), (_, read_timeout_seconds)] = phase_timeouts;
+        let max_concurrency = parse_var("MAX_CONCURRENCY", "a positive integer")?
+            .unwrap_or(DEFAULT_MAX_CONCURRENCY);
+        if max_concurrency == 0 {
+            return Err(invalid("MAX_CONCURRENCY", "a positive integer", "0"));
+        }
+
+        let mut sensitive_headers: Vec<HeaderName> = redact::DEFAULT_SENSITIVE_HEADERS
+            .iter()
+            .map(|name| HeaderName::from_static(name))
+            .collect();
+        let extra_headers = env::var("SENSITIVE_HEADERS").unwrap_or_default();
+        for name in extra_headers.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| invalid("SENSITIVE_HEADERS", "comma-separated header names", name))?;
+            if !sensitive_headers.contains(&name) {
+                sensitive_headers.push(name);
+            }
+        }
+
+        let browser_profile = match env::var("DEFAULT_BROWSER_PROFILE") {
+            Ok(value) => BrowserProfile::parse(&value)
+                .ok_or_else(|| invalid("DEFAULT_BROWSER_PROFILE", "chrome, firefox or none", &value))?,
+            Err(_) => BrowserProfile::None,
+        };
+
+        let strip_response_headers = env::var("STRIP_RESPONSE_HEADERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| invalid("STRIP_RESPONSE_HEADERS", "comma-separated header names", name))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let allowed_content_types = env::var("ALLOWED_CONTENT_TYPES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| {
+                extract::content_type_pattern(pattern).ok_or_else(|| {
+                    invalid("ALLOWED_CONTENT_TYPES", "comma-separated media types like text/html or text/*", pattern)
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let max_response_headers = parse_var("MAX_RESPONSE_HEADERS", "a positive number of headers")?
+            .unwrap_or(DEFAULT_MAX_RESPONSE_HEADERS);
+        if max_response_headers == 0 {
+            return Err(invalid("MAX_RESPONSE_HEADERS", "a positive number of headers", "0"));
+        }
+        let max_response_header_bytes = parse_var("MAX_RESPONSE_HEADER_BYTES", "a positive number of bytes")?
+            .unwrap_or(DEFAULT_MAX_RESPONSE_HEADER_BYTES);
+        if max_response_header_bytes == 0 {
+            return Err(invalid("MAX_RESPONSE_HEADER_BYTES", "a positive number of bytes", "0"));
+        }
+
+        let rate_limit_per_minute = parse_var("RATE_LIMIT_PER_MINUTE", "a positive integer")?
+            .unwrap_or(DEFAULT_RATE_LIMIT_PER_MINUTE);
+        if rate_limit_per_minute == 0 {
+            return Err(invalid("RATE_LIMIT_PER_MINUTE", "a positive integer", "0"));
+        }
+
+        let max_url_length = parse_var("MAX_URL_LENGTH", "a positive number of bytes")?
+            .unwrap_or(DEFAULT_MAX_URL_LENGTH);
+        if max_url_length == 0 {
+            return Err(invalid("MAX_URL_LENGTH", "a positive number of bytes", "0"));
+        }
+
+        let hard_timeout = parse_var::<u64>("HARD_TIMEOUT_SECONDS", "a positive number of seconds")?;
+        if hard_timeout == Some(0) {
+            return Err(invalid("HARD_TIMEOUT_SECONDS", "a positive number of seconds", "0"));
+        }
+
+        let pool_idle_timeout = parse_var("POOL_IDLE_TIMEOUT_SECONDS", "a positive number of seconds")?
+            .unwrap_or(DEFAULT_POOL_IDLE_TIMEOUT_SECONDS);
+        if pool_idle_timeout == 0 {
+            return Err(invalid("POOL_IDLE_TIMEOUT_SECONDS", "a positive number of seconds", "0"));
+        }
+
+        let tor_control_addr = env::var("TOR_CONTROL_ADDR")
+            .ok()
+            .map(|addr| addr.trim().to_string())
+            .filter(|addr| !addr.is_empty());
+        if let Some(addr) = &tor_control_addr {
+            let port = addr.rsplit_once(':').and_then(|(_, port)| port.parse::<u16>().ok());
+            if port.is_none_or(|port| port == 0) {
+                return Err(invalid("TOR_CONTROL_ADDR", "a host:port address", addr));
+            }
+        }
+        let tor_control_password = env::var("TOR_CONTROL_PASSWORD").ok().filter(|password| !password.is_empty());
+        if tor_control_password.is_some() && tor_control_addr.is_none() {
+            return Err(ConfigError("TOR_CONTROL_PASSWORD requires TOR_CONTROL_ADDR".to_string()));
+        }
+
+        let exit_ip_url = env::var("EXIT_IP_URL")
+            .map(|url| url.trim().to_string())
+            .unwrap_or_else(|_| DEFAULT_EXIT_IP_URL.to_string());
+        if !url::Url::parse(&exit_ip_url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+            return Err(invalid("EXIT_IP_URL", "an http(s) URL", &exit_ip_url));
+        }
+
+        let job_workers = parse_var("JOB_WORKERS", "a positive integer")?.unwrap_or(DEFAULT_JOB_WORKERS);
+        if job_workers == 0 {
+            return Err(invalid("JOB_WORKERS", "a positive integer", "0"));
+        }
+
+        Ok(Config {
+            host,
+            port,
+            default_proxy,
+            user_agent: env::var("DEFAULT_USER_AGENT").ok(),
+            accept_language: env::var("DEFAULT_ACCEPT_LANGUAGE")
+                .ok()
+                .map(|value| {
+                    HeaderValue::from_str(value.trim())
+                        .map_err(|_| invalid("DEFAULT_ACCEPT_LANGUAGE", "a header value", &value))
+                })
+                .transpose()?,
+            browser_profile,
+            proxy_pool,
+            proxy_chain,
+            timeout_seconds,
+            connect_timeout_seconds,
+            read_timeout_seconds,
+            max_retries: parse_var::<u32>("MAX_RETRIES", "a non-negative integer")?
+                .unwrap_or(0)
+                .min(MAX_RETRIES_LIMIT),
+            max_response_bytes: parse_var("MAX_RESPONSE_BYTES", "a number of bytes")?,
+            allowed_content_types,
+            max_response_headers,
+            max_response_header_bytes,
+            max_retry_after: Duration::from_secs(
+                parse_var("MAX_RETRY_AFTER_SECONDS", "a number of seconds")?
+                    .unwrap_or(DEFAULT_MAX_RETRY_AFTER_SECONDS),
+            ),
+            max_concurrency,
+            max_in_flight_requests: Some(
+                parse_var("MAX_IN_FLIGHT_REQUESTS", "a number of requests")?.unwrap_or(DEFAULT_MAX_IN_FLIGHT_REQUESTS),
+            )
+            .filter(|&limit| limit > 0),
+            max_url_length,
+            shutdown_timeout: Duration::from_secs(
+                parse_var("SHUTDOWN_TIMEOUT_SECONDS", "a number of seconds")?
+                    .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECONDS),
+            ),
+            ssrf_guard: Arc::new(SsrfGuard::from_env()),
+            sensitive_headers,
+            strip_response_headers,
+            respect_robots: parse_bool_var("RESPECT_ROBOTS")?.unwrap_or(false),
+            robots_cache_ttl: Duration::from_secs(
+                parse_var("ROBOTS_CACHE_TTL_SECONDS", "a number of seconds")?
+                    .unwrap_or(DEFAULT_ROBOTS_CACHE_TTL_SECONDS),
+            ),
+            response_cache_max_entries: parse_var("RESPONSE_CACHE_MAX_ENTRIES", "a number of entries")?
+                .unwrap_or(DEFAULT_RESPONSE_CACHE_MAX_ENTRIES),
+            api_token: env::var("API_TOKEN").ok().filter(|token| !token.is_empty()),
+            api_keys: env::var("API_KEYS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(String::from)
+                .collect(),
+            rate_limit_per_minute,
+            job_workers,
+            job_ttl: Duration::from_secs(
+                parse_var("JOB_TTL_SECONDS", "a number of seconds")?.unwrap_or(DEFAULT_JOB_TTL_SECONDS),
+            ),
+            per_host_delay: Duration::from_millis(
+                parse_var("PER_HOST_DELAY_MS", "a number of milliseconds")?.unwrap_or(0),
+            ),
+            circuit_breaker_threshold: parse_var("CIRCUIT_BREAKER_THRESHOLD", "a number of failures")?
+                .unwrap_or(DEFAULT_CIRCUIT_BREAKER_THRESHOLD),
+            circuit_breaker_cooldown: Duration::from_secs(
+                parse_var("CIRCUIT_BREAKER_COOLDOWN_SECONDS", "a number of seconds")?
+                    .unwrap_or(DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECONDS),
+            ),
+            session_ttl: Duration::from_secs(
+                parse_var("SESSION_TTL_SECONDS", "a number of seconds")?.unwrap_or(DEFAULT_SESSION_TTL_SECONDS),
+            ),
+            allow_insecure_tls: parse_bool_var("ALLOW_INSECURE_TLS")?.unwrap_or(false),
+            ca_certificates: load_ca_certificates()?,
+            client_identity: load_client_identity()?,
+            dns_resolver: env::var("DNS_RESOLVER")
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| {
+                    dns::build_resolver(&value)
+                        .map(Arc::new)
+                        .map_err(|e| ConfigError(format!("Invalid DNS_RESOLVER: {}", e)))
+                })
+                .transpose()?,
+            hard_timeout: hard_timeout.map(Duration::from_secs),
+            access_log: parse_bool_var("ACCESS_LOG")?.unwrap_or(false),
+            compress_responses: parse_bool_var("COMPRESS_RESPONSES")?.unwrap_or(false),
+            pool_max_idle_per_host: parse_var("POOL_MAX_IDLE_PER_HOST", "a number of connections")?
+                .unwrap_or(DEFAULT_POOL_MAX_IDLE_PER_HOST),
+            pool_idle_timeout: Duration::from_secs(pool_idle_timeout),
+            tcp_keepalive: Some(
+                parse_var("TCP_KEEPALIVE_SECONDS", "a number of seconds")?.unwrap_or(DEFAULT_TCP_KEEPALIVE_SECONDS),
+            )
+            .filter(|&seconds| seconds > 0)
+            .map(Duration::from_secs),
+            tor_control_addr,
+            tor_control_password,
+            exit_ip_url,
+            exit_ip_cache_ttl: Duration::from_secs(
+                parse_var("EXIT_IP_CACHE_TTL_SECONDS", "a number of seconds")?
+                    .unwrap_or(DEFAULT_EXIT_IP_CACHE_TTL_SECONDS),
+            ),
+        })
+    }
+
+    /// Effective response size limit: the smaller of `MAX_RESPONSE_BYTES` and
+    /// the per-request limit, or unlimited when neither is set.
+    pub fn max_response_bytes(&self, requested: Option<usize>) -> Option<usize> {
+        match (self.max_response_bytes, requested) {
+            (Some(configured), Some(requested)) => Some(configured.min(requested)),
+            (configured, requested) => configured.or(requested),
+        }
+    }
+}
+
+/// Parses an environment variable, giving `None` when it's unset.
+fn parse_var<T: FromStr>(name: &str, expected: &str) -> Result<Option<T>, ConfigError> {
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| invalid(name, expected, &value)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parses a boolean environment variable (1/true/yes or 0/false/no),
+/// giving `None` when it's unset.
+fn parse_bool_var(name: &str) -> Result<Option<bool>, ConfigError> {
+    match env::var(name) {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" => Ok(Some(true)),
+            "0" | "false" | "no" => Ok(Some(false)),
+            _ => Err(invalid(name, "true or false", &value)),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+/// Loads the PEM bundle named by TLS_CA_BUNDLE, if set, failing when it
+/// can't be read or holds no certificate.
+fn load_ca_certificates() -> Result<Vec<Certificate>, ConfigError> {
+    let Some(path) = env::var("TLS_CA_BUNDLE").ok().filter(|path| !path.is_empty()) else {
+        return Ok(Vec::new());
+    };
+    let pem = read_file("TLS_CA_BUNDLE", &path)?;
+    let certificates = Certificate::from_pem_bundle(&pem)
+        .map_err(|e| ConfigError(format!("Invalid TLS_CA_BUNDLE: {} is not a PEM bundle ({})", path, e)))?;
+    if certificates.is_empty() {
+        return Err(ConfigError(format!("Invalid TLS_CA_BUNDLE: no certificate found in {}", path)));
+    }
+    Ok(certificates)
+}
+
+/// Loads the client certificate named by TLS_CLIENT_CERT, if set, with the
+/// private key from TLS_CLIENT_KEY or, without it, from the same PEM file.
+fn load_client_identity() -> Result<Option<Identity>, ConfigError> {
+    let cert_path = env::var("TLS_CLIENT_CERT").ok().filter(|path| !path.is_empty());
+    let key_path = env::var("TLS_CLIENT_KEY").ok().filter(|path| !path.is_empty());
+    let Some(cert_path) = cert_path else {
+        return match key_path {
+            Some(_) => Err(ConfigError("TLS_CLIENT_KEY requires TLS_CLIENT_CERT".to_string())),
+            None => Ok(None),
+        };
+    };
+    let mut pem = read_file("TLS_CLIENT_CERT", &cert_path)?;
+    if let Some(key_path) = &key_path {
+        pem.push(b'\n');
+        pem.extend(read_file("TLS_CLIENT_KEY", key_path)?);
+    }
+    Identity::from_pem(&pem).map(Some).map_err(|e| {
+        ConfigError(format!(
+            "Invalid TLS_CLIENT_CERT: {} needs a PEM certificate and private key ({})",
+            cert_path, e
+        ))
+    })
+}
+
+fn read_file(name: &str, path: &str) -> Result<Vec<u8>, ConfigError> {
+    fs::read(path).map_err(|e| ConfigError(format!("Invalid {}: can't read {} ({})", name, path, e)))
+}
+
+fn check_proxy(name: &str, proxy: &str) -> Result<(), ConfigError> {
+    Proxy::all(proxy)
+        .map(|_| ())
+        .map_err(|e| ConfigError(format!("Invalid {}: {} ({})", name, redact::proxy_url(proxy), e)))
+}
+
+fn invalid(name: &str, expected: &str, value: &str) -> ConfigError {
+    ConfigError(format!("Invalid {}: expected {}, got '{}'", name, expected, value))
+}
@@ -0,0 +1,117 @@
+// cookies.rs
+//
+// Set-Cookie parsing for `include_cookies`, after RFC 6265 section 5.2: the
+// name and value come before the first semicolon, and the attributes after it
+// are matched case-insensitively, unknown ones being ignored. Each header is
+// parsed on its own, as they're never joined: commas can't separate cookies,
+// since Expires dates hold them.
+use serde::Serialize;
+
+/// A cookie set by the target.
+#[derive(Serialize, Clone, Debug)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    // Domain the cookie is sent to, without a leading dot; only the host
+    // that set it when missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    // Expiry date as the target wrote it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    // Lifetime in seconds, overriding `expires`; 0 or less deletes the cookie
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<i64>,
+    pub http_only: bool,
+    pub secure: bool,
+    // "Strict", "Lax" or "None", as the target wrote it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub same_site: Option<String>,
+}
+
+/// Parses one Set-Cookie header value, giving `None` when it holds no
+/// name=value pair, which user agents ignore.
+pub fn parse_set_cookie(header: &str) -> Option<Cookie> {
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().trim_matches('"').to_string(),
+        domain: None,
+        path: None,
+        expires: None,
+        max_age: None,
+        http_only: false,
+        secure: false,
+        same_site: None,
+    };
+    for attribute in parts {
+        let (key, value) = match attribute.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (attribute.trim(), ""),
+        };
+        // The last occurrence of an attribute wins
+        match key.to_ascii_lowercase().as_str() {
+            "domain" if !value.is_empty() => {
+                cookie.domain = Some(value.trim_start_matches('.').to_ascii_lowercase());
+            }
+            "path" if value.starts_with('/') => cookie.path = Some(value.to_string()),
+            "expires" if !value.is_empty() => cookie.expires = Some(value.to_string()),
+            "max-age" => {
+                if let Ok(seconds) = value.parse() {
+                    cookie.max_age = Some(seconds);
+                }
+            }
+            "httponly" => cookie.http_only = true,
+            "secure" => cookie.secure = true,
+            "samesite" if !value.is_empty() => cookie.same_site = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Some(cookie)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_the_pair_and_attributes() {
+        let cookie = parse_set_cookie(
+            "session=\"abc=123\"; Domain=.Example.COM; Path=/app; Expires=Wed, 21 Oct 2026 07:28:00 GMT; \
+             Max-Age=3600; HttpOnly; secure; SameSite=Lax; Priority=High",
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "session");
+        assert_eq!(cookie.value, "abc=123");
+        assert_eq!(cookie.domain.as_deref(), Some("example.com"));
+        assert_eq!(cookie.path.as_deref(), Some("/app"));
+        assert_eq!(cookie.expires.as_deref(), Some("Wed, 21 Oct 2026 07:28:00 GMT"));
+        assert_eq!(cookie.max_age, Some(3600));
+        assert!(cookie.http_only && cookie.secure);
+        assert_eq!(cookie.same_site.as_deref(), Some("Lax"));
+    }
+
+    #[test]
+    fn parse_ignores_invalid_attributes_and_keeps_the_last() {
+        let cookie = parse_set_cookie("id=1; Path=relative; Max-Age=soon; Domain=; Path=/a; Path=/b").unwrap();
+        assert_eq!(cookie.path.as_deref(), Some("/b"));
+        assert_eq!(cookie.max_age, None);
+        assert_eq!(cookie.domain, None);
+        assert!(!cookie.http_only && !cookie.secure);
+        assert_eq!(parse_set_cookie("empty=").unwrap().value, "");
+    }
+
+    #[test]
+    fn parse_needs_a_name_value_pair() {
+        assert!(parse_set_cookie("no-pair; Path=/").is_none());
+        assert!(parse_set_cookie("=value").is_none());
+        assert!(parse_set_cookie("").is_none());
+    }
+}
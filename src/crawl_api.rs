@@ -0,0 +1,235 @@
+// crawl_api.rs
+//
+// POST /crawl: a breadth-first crawl from a seed URL, following the links of
+// each HTML page up to a depth and page count, optionally on the seed's host only.
+use crate::{
+    check_robots, error_body, fetch, normalize_url, request_id, select_client, with_hard_timeout, with_request_id,
+    ClientOptions, FetchOptions, Fetched, ProxyFields, ScrapeError, SelectedClient,
+};
+use crate::circuit_breaker::CircuitBreakers;
+use crate::config::Config;
+use crate::extract;
+use crate::politeness::HostThrottle;
+use crate::proxy_pool::ProxyPool;
+use crate::robots::RobotsCache;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
+use futures::future;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::Semaphore;
+use tracing::{debug, field, info, info_span, Instrument, Span};
+
+// Link depth and page count of a crawl that doesn't set `max_depth` or `max_pages`
+const DEFAULT_CRAWL_DEPTH: usize = 1;
+const DEFAULT_CRAWL_PAGES: usize = 50;
+// Most pages one crawl fetches, whatever it asks for
+const MAX_CRAWL_PAGES: usize = 500;
+
+// Define the structure for the incoming crawl POST request
+#[derive(Deserialize)]
+pub struct CrawlRequest {
+    // The seed URL the crawl starts from, at depth 0
+    url: String,
+    // Optional number of link hops followed from the seed, 1 by default
+    max_depth: Option<usize>,
+    // Optional number of pages fetched, seed included; 50 by default and at most MAX_CRAWL_PAGES
+    max_pages: Option<usize>,
+    // Optional flag keeping the crawl on the seed's host, on by default
+    same_domain: Option<bool>,
+    // Optional flag returning each page's decoded text body in `content`
+    include_content: Option<bool>,
+    // Optional proxy and timeouts, the timeouts applying to each page
+    #[serde(flatten)]
+    proxy_fields: ProxyFields,
+    // Optional robots.txt check, as in `ScrapeRequest`, applied to each page
+    respect_robots: Option<bool>,
+}
+
+// One page of a crawl
+#[derive(Serialize)]
+struct CrawledPage {
+    // Link hops from the seed
+    depth: usize,
+    // Upstream HTTP status, absent if no response was received
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    // Title of an HTML page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    // Number of distinct links on an HTML page, followed or not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    links: Option<usize>,
+    // The decoded body of a text page, with `include_content`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+// Define the structure for the outgoing crawl JSON response
+#[derive(Serialize)]
+struct CrawlResponse {
+    // Every page fetched, by URL as requested
+    pages: HashMap<String, CrawledPage>,
+}
+
+/// Handles the POST request to crawl from a seed URL.
+///
+/// Pages are fetched breadth-first, one depth level at a time, with the same
+/// proxy, timeout, politeness and robots.txt rules as `batch_scrape_handler`,
+/// and each fetch holds a permit from the shared batch semaphore. Links are
+/// taken from HTML pages only. Every URL is fetched at most once, and no new
+/// URL is queued once `max_pages` have been, so cycles end the crawl early
+/// rather than looping.
+pub async fn crawl_handler(
+    http_req: HttpRequest,
+    req: web::Json<CrawlRequest>,
+    config: web::Data<Config>,
+    base_client: web::Data<Client>,
+    proxy_pool: web::Data<ProxyPool>,
+    semaphore: web::Data<Semaphore>,
+    robots_cache: web::Data<RobotsCache>,
+    throttle: web::Data<HostThrottle>,
+    breakers: web::Data<CircuitBreakers>,
+) -> impl Responder {
+    let request_id = request_id(&http_req);
+    let span = info_span!("crawl", request_id = %request_id, url = %req.url, proxy = field::Empty);
+
+    let prepared: Result<_, ScrapeError> = span.in_scope(|| {
+        let seed = normalize_url(&config, &req.url)?;
+        let client_options = ClientOptions::from_proxy_fields(&req.proxy_fields)?;
+        let client = select_client(&config, &base_client, &proxy_pool, &breakers, &client_options)?;
+        Ok((seed, client))
+    });
+    let (seed, client) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            let response = HttpResponse::build(e.status_code()).json(error_body(&e));
+            return with_request_id(response, &request_id);
+        }
+    };
+
+    let max_depth = req.max_depth.unwrap_or(DEFAULT_CRAWL_DEPTH);
+    let max_pages = req.max_pages.unwrap_or(DEFAULT_CRAWL_PAGES).clamp(1, MAX_CRAWL_PAGES);
+    let same_host = req.same_domain.unwrap_or(true);
+    let seed_host = url::Url::parse(&seed)
+        .expect("normalized URLs parse")
+        .host_str()
+        .map(str::to_string);
+    span.in_scope(|| info!(max_depth, max_pages, same_host, "Starting crawl"));
+
+    let options = FetchOptions::new(&config);
+    let respect_robots = req.respect_robots.unwrap_or(config.respect_robots);
+    let include_content = req.include_content.unwrap_or(false);
+    let mut queued = HashSet::from([seed.clone()]);
+    let mut frontier = vec![seed];
+    let mut pages = HashMap::new();
+    for depth in 0..=max_depth {
+        if frontier.is_empty() {
+            break;
+        }
+        let level = future::join_all(frontier.iter().map(|url| {
+            let url_span = info_span!(parent: &span, "scrape", url = %url, depth, status = field::Empty);
+            async {
+                let _permit = semaphore.acquire().await.expect("semaphore is never closed");
+                crawl_page(&config, &throttle, &client, &robots_cache, url, &options, respect_robots).await
+            }
+            .instrument(url_span)
+        }))
+        .await;
+
+        let mut next = Vec::new();
+        for (url, (result, links)) in frontier.into_iter().zip(level) {
+            if depth < max_depth {
+                for link in links {
+                    if queued.len() >= max_pages {
+                        break;
+                    }
+                    // Held to the same rules as the seed, MAX_URL_LENGTH included
+                    let link = match normalize_url(&config, &link) {
+                        Ok(link) => link,
+                        Err(e) => {
+                            debug!(url = %url, error = %e, "Skipping link");
+                            continue;
+                        }
+                    };
+                    let on_seed_host = url::Url::parse(&link).ok().and_then(|link| link.host_str().map(str::to_string));
+                    if same_host && on_seed_host != seed_host {
+                        continue;
+                    }
+                    if queued.insert(link.clone()) {
+                        next.push(link);
+                    }
+                }
+            }
+            let page = match result {
+                Ok((fetched, title, links)) => CrawledPage {
+                    depth,
+                    status: Some(fetched.meta.status.as_u16()),
+                    title,
+                    links,
+                    content: (include_content && fetched.binary.is_none()).then_some(fetched.content),
+                    error: None,
+                    error_code: None,
+                },
+                Err(e) => CrawledPage {
+                    depth,
+                    status: e.response_meta().map(|meta| meta.status.as_u16()),
+                    title: None,
+                    links: None,
+                    content: None,
+                    error: Some(e.to_string()),
+                    error_code: Some(e.code()),
+                },
+            };
+            pages.insert(url, page);
+        }
+        frontier = next;
+    }
+
+    span.in_scope(|| info!(pages = pages.len(), "Crawl finished"));
+    with_request_id(HttpResponse::Ok().json(CrawlResponse { pages }), &request_id)
+}
+
+// A crawled page with its title and link count, or what went wrong, and the links to follow from it
+type CrawlOutcome = (Result<(Fetched, Option<String>, Option<usize>), ScrapeError>, Vec<String>);
+
+/// Fetches one page of a crawl, extracting the title and links of an HTML page.
+async fn crawl_page(
+    config: &Config,
+    throttle: &HostThrottle,
+    client: &SelectedClient,
+    robots_cache: &RobotsCache,
+    url: &str,
+    options: &FetchOptions,
+    respect_robots: bool,
+) -> CrawlOutcome {
+    let operation = async {
+        if respect_robots {
+            check_robots(config, throttle, robots_cache, client, url, options).await?;
+        }
+        fetch(config, throttle, client, url, options).await.result
+    };
+    let result = with_hard_timeout(config, operation).await;
+    let status = result.as_ref().map_or_else(|e| e.status_code(), |_| StatusCode::OK);
+    Span::current().record("status", status.as_u16());
+
+    let Ok(fetched) = result else {
+        return (result.map(|fetched| (fetched, None, None)), Vec::new());
+    };
+    let content_type = fetched.meta.headers.get("content-type").map(String::as_str);
+    if fetched.binary.is_some() || !extract::is_html(content_type) {
+        return (Ok((fetched, None, None)), Vec::new());
+    }
+    let base_url = url::Url::parse(&fetched.meta.final_url).expect("final URL comes from a parsed response URL");
+    let links: Vec<String> = extract::extract_links(&fetched.content, &base_url, None)
+        .into_iter()
+        .map(|link| link.url)
+        .collect();
+    let title = extract::extract_metadata(&fetched.content, &base_url).title;
+    let count = links.len();
+    (Ok((fetched, title, Some(count))), links)
+}
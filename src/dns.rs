@@ -0,0 +1,69 @@
+// dns.rs
+//
+// Resolution through a specific DNS server, from DNS_RESOLVER, for
+// environments where the system resolver leaks queries or is blocked. The
+// resolver only replaces the lookup itself: `GuardedResolver` still vets every
+// address it returns.
+use hickory_resolver::config::{LookupIpStrategy, NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::TokioAsyncResolver;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+// Port of plain DNS servers given without one
+const DNS_PORT: u16 = 53;
+// Path DNS-over-HTTPS queries are sent to; hickory doesn't let it be changed
+const DOH_PATH: &str = "/dns-query";
+
+/// Builds a resolver for a DNS_RESOLVER value: either a plain DNS server as
+/// `ip` or `ip:port`, or a DNS-over-HTTPS endpoint as
+/// `https://host[:port]/dns-query`.
+///
+/// A DNS-over-HTTPS host given by name is looked up once, here, with the
+/// system resolver; its certificate is then checked against that name.
+pub fn build_resolver(value: &str) -> Result<TokioAsyncResolver, String> {
+    let value = value.trim();
+    let servers = if value.starts_with("https://") {
+        doh_servers(value)?
+    } else {
+        let addr = match value.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, DNS_PORT),
+            Err(_) => value
+                .parse::<SocketAddr>()
+                .map_err(|_| format!("expected ip[:port] or https://host/dns-query, got '{}'", value))?,
+        };
+        NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true)
+    };
+
+    // Every address is asked for, so the SSRF checks see all of them
+    let mut options = ResolverOpts::default();
+    options.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+    Ok(TokioAsyncResolver::tokio(
+        ResolverConfig::from_parts(None, Vec::new(), servers),
+        options,
+    ))
+}
+
+/// The name servers of a DNS-over-HTTPS endpoint URL.
+fn doh_servers(value: &str) -> Result<NameServerConfigGroup, String> {
+    let url = url::Url::parse(value).map_err(|e| format!("{} ({})", value, e))?;
+    if !matches!(url.path(), "" | "/" | DOH_PATH) || url.query().is_some() {
+        return Err(format!("DNS-over-HTTPS is only supported at {}, got '{}'", DOH_PATH, value));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("no host in '{}'", value))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(443);
+    let ips: Vec<IpAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => (host, port)
+            .to_socket_addrs()
+            .map_err(|e| format!("can't resolve {} ({})", host, e))?
+            .map(|addr| addr.ip())
+            .collect(),
+    };
+    if ips.is_empty() {
+        return Err(format!("{} has no addresses", host));
+    }
+    Ok(NameServerConfigGroup::from_ips_https(&ips, port, host.to_string(), true))
+}
@@ -0,0 +1,137 @@
+// download_api.rs
+//
+// POST /download: a target's body streamed to the caller as it arrives, with
+// the upstream Content-Type, instead of being buffered into a JSON response.
+use crate::{
+    check_robots, collect_headers, error_body, normalize_url, parse_headers, referer_header, request_id, select_client,
+    send_following_redirects, set_cookies, strip_headers, with_hard_timeout, with_request_id, ClientOptions,
+    FetchOptions, ProxyFields, ResponseMeta, ScrapeError,
+};
+use crate::circuit_breaker::CircuitBreakers;
+use crate::config::Config;
+use crate::politeness::HostThrottle;
+use crate::proxy_pool::ProxyPool;
+use crate::robots::RobotsCache;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use futures::StreamExt;
+use reqwest::Client;
+use reqwest::header::{HeaderValue, CONTENT_ENCODING, CONTENT_TYPE, REFERER, USER_AGENT};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Instant;
+use tracing::{field, info, info_span, warn, Instrument};
+
+// Define the structure for the incoming download POST request
+#[derive(Deserialize)]
+pub struct DownloadRequest {
+    url: String,
+    // Optional proxy and timeouts, the timeout covering the whole transfer
+    #[serde(flatten)]
+    proxy_fields: ProxyFields,
+    // Optional extra headers, User-Agent and Referer, as in `ScrapeRequest`
+    headers: Option<HashMap<String, String>>,
+    user_agent: Option<String>,
+    referer: Option<String>,
+    // Optional robots.txt check, as in `ScrapeRequest`
+    respect_robots: Option<bool>,
+}
+
+/// Handles the POST request to download a URL.
+///
+/// Picks the client the same way as `scrape_handler` and GETs the URL, then
+/// streams the upstream body straight to the caller with its status,
+/// Content-Type and Content-Length, so large files are never buffered. There
+/// are no retries and MAX_RESPONSE_BYTES doesn't apply. Anything that fails
+/// before the body starts, including a non-2xx answer, gets the usual JSON error.
+pub async fn download_handler(
+    http_req: HttpRequest,
+    req: web::Json<DownloadRequest>,
+    config: web::Data<Config>,
+    base_client: web::Data<Client>,
+    proxy_pool: web::Data<ProxyPool>,
+    robots_cache: web::Data<RobotsCache>,
+    throttle: web::Data<HostThrottle>,
+    breakers: web::Data<CircuitBreakers>,
+) -> impl Responder {
+    let request_id = request_id(&http_req);
+    let span = info_span!(
+        "download",
+        request_id = %request_id,
+        url = %req.url,
+        proxy = field::Empty,
+        status = field::Empty,
+    );
+
+    let operation = async {
+        let url = normalize_url(&config, &req.url)?;
+        let mut options = FetchOptions::new(&config);
+        options.headers = parse_headers(req.headers.as_ref())?;
+        if let Some(user_agent) = &req.user_agent {
+            let value = HeaderValue::from_str(user_agent)
+                .map_err(|_| ScrapeError::InvalidHeader(USER_AGENT.to_string()))?;
+            options.headers.insert(USER_AGENT, value);
+        }
+        if let Some(referer) = &req.referer {
+            options.headers.insert(REFERER, referer_header(referer)?);
+        }
+        let client_options = ClientOptions {
+            // The body is streamed to the caller rather than read here
+            read_timeout_seconds: None,
+            ..ClientOptions::from_proxy_fields(&req.proxy_fields)?
+        };
+        let client = select_client(&config, &base_client, &proxy_pool, &breakers, &client_options)?;
+
+        if req.respect_robots.unwrap_or(config.respect_robots) {
+            check_robots(&config, &throttle, &robots_cache, &client, &url, &options).await?;
+        }
+
+        info!("Starting download");
+        let started = Instant::now();
+        let (response, redirects) = send_following_redirects(&config, &throttle, &client, &url, &options).await?;
+        if !response.status().is_success() {
+            let meta = ResponseMeta {
+                status: response.status(),
+                version: response.version(),
+                headers: collect_headers(response.headers()),
+                final_url: response.url().to_string(),
+                redirects,
+                headers_time: started.elapsed(),
+                body_time: None,
+                set_cookies: set_cookies(response.headers()),
+            };
+            warn!(status = meta.status.as_u16(), "Failed to download URL");
+            return Err(ScrapeError::Status(Box::new(meta)));
+        }
+        Ok(response)
+    };
+    let result = with_hard_timeout(&config, operation).instrument(span.clone()).await;
+
+    let response = match result {
+        Ok(upstream) => {
+            span.record("status", upstream.status().as_u16());
+            span.in_scope(|| info!(content_length = upstream.content_length(), "Streaming download"));
+            let mut response = HttpResponse::build(upstream.status());
+            // A Content-Encoding left on the response means the body is still encoded
+            for name in [CONTENT_TYPE, CONTENT_ENCODING] {
+                if let Some(value) = upstream.headers().get(&name) {
+                    response.insert_header((name, value.clone()));
+                }
+            }
+            // Without a known length the body goes out chunked
+            if let Some(length) = upstream.content_length() {
+                response.no_chunking(length);
+            }
+            let body = upstream.bytes_stream().inspect(move |chunk| {
+                if let Err(e) = chunk {
+                    span.in_scope(|| warn!(error = %e, "Download interrupted"));
+                }
+            });
+            response.streaming(body)
+        }
+        Err(e) => {
+            span.record("status", e.status_code().as_u16());
+            HttpResponse::build(e.status_code()).json(strip_headers(&config, error_body(&e)))
+        }
+    };
+    with_request_id(response, &request_id)
+}
@@ -0,0 +1,332 @@
+// extract.rs
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use std::collections::HashSet;
+use url::Url;
+
+/// Parses a CSS selector, describing the problem on a single line when it's invalid.
+pub fn parse_selector(selector: &str) -> Result<Selector, String> {
+    Selector::parse(selector).map_err(|e| e.to_string().split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Whether a Content-Type header value denotes an HTML document. A missing
+/// header is given the benefit of the doubt.
+pub fn is_html(content_type: Option<&str>) -> bool {
+    let Some(content_type) = content_type else {
+        return true;
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    essence.eq_ignore_ascii_case("text/html") || essence.eq_ignore_ascii_case("application/xhtml+xml")
+}
+
+/// Normalizes an `allowed_content_types` entry: a media type like
+/// `text/html`, a wildcard subtype like `text/*`, or `*/*`. Gives `None` for
+/// anything else, parameters included.
+pub fn content_type_pattern(pattern: &str) -> Option<String> {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    let (kind, subtype) = pattern.split_once('/')?;
+    let is_token = |part: &str| {
+        !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"!#$&-^_.+".contains(&byte))
+    };
+    let valid = match (kind, subtype) {
+        ("*", "*") => true,
+        ("*", _) => false,
+        (kind, "*") => is_token(kind),
+        (kind, subtype) => is_token(kind) && is_token(subtype),
+    };
+    valid.then_some(pattern)
+}
+
+/// Whether a Content-Type header value matches one of `patterns`, each
+/// normalized by `content_type_pattern`. A missing header is given the
+/// benefit of the doubt.
+pub fn content_type_allowed(patterns: &[String], content_type: Option<&str>) -> bool {
+    let Some(content_type) = content_type else {
+        return true;
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    let kind = essence.split('/').next().unwrap_or_default();
+    patterns.iter().any(|pattern| match pattern.strip_suffix("/*") {
+        Some("*") => true,
+        Some(pattern_kind) => pattern_kind == kind,
+        None => *pattern == essence,
+    })
+}
+
+/// Returns the outer HTML of every element in `html` matching `selector`, in document order.
+pub fn select_outer_html(html: &str, selector: &Selector) -> Vec<String> {
+    Html::parse_document(html)
+        .select(selector)
+        .map(|element| element.html())
+        .collect()
+}
+
+/// Returns the readable text of every element in `html` matching `selector`, in document order.
+pub fn select_text(html: &str, selector: &Selector) -> Vec<String> {
+    Html::parse_document(html)
+        .select(selector)
+        .map(|element| {
+            let mut text = String::new();
+            collect_text(element, &mut text);
+            collapse_whitespace(&text)
+        })
+        .collect()
+}
+
+// Elements whose contents are never visible text
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "noscript", "template", "head"];
+
+// Elements that start a new line of text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "br", "dd", "div", "dl", "dt", "figcaption",
+    "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "main",
+    "nav", "ol", "p", "pre", "section", "table", "td", "th", "tr", "ul",
+];
+
+/// Converts an HTML document to readable plain text: tags are stripped,
+/// script and style contents dropped, and whitespace collapsed, keeping one
+/// line per block element.
+pub fn html_to_text(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let mut text = String::new();
+    collect_text(document.root_element(), &mut text);
+    collapse_whitespace(&text)
+}
+
+fn collect_text(element: ElementRef<'_>, out: &mut String) {
+    for child in element.children() {
+        if let Some(text) = child.value().as_text() {
+            // Line breaks in the source are just whitespace; lines come from blocks
+            out.extend(text.chars().map(|c| if c == '\n' || c == '\r' { ' ' } else { c }));
+        } else if let Some(child) = ElementRef::wrap(child) {
+            let name = child.value().name();
+            if SKIPPED_ELEMENTS.contains(&name) {
+                continue;
+            }
+            let is_block = BLOCK_ELEMENTS.contains(&name);
+            if is_block {
+                out.push('\n');
+            }
+            collect_text(child, out);
+            if is_block {
+                out.push('\n');
+            }
+        }
+    }
+}
+
+/// Collapses runs of whitespace within each line and drops empty lines.
+fn collapse_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A hyperlink found on a page.
+#[derive(Serialize)]
+pub struct Link {
+    // Absolute URL, without fragment
+    pub url: String,
+    // Visible anchor text, whitespace-collapsed
+    pub text: String,
+}
+
+/// Extracts every `<a href>` from `html` (or from the elements matching
+/// `scope`, if given) as absolute http(s) URLs resolved against `base_url`,
+/// honouring a `<base href>` in the document.
+///
+/// Fragments are stripped, fragment-only links to the page itself and
+/// non-http(s) schemes such as `mailto:` are dropped, and duplicates keep the
+/// first anchor text seen.
+pub fn extract_links(html: &str, base_url: &Url, scope: Option<&Selector>) -> Vec<Link> {
+    let document = Html::parse_document(html);
+    let anchors = Selector::parse("a[href]").expect("static selector is valid");
+    let base_href = Selector::parse("base[href]").expect("static selector is valid");
+
+    let base_url = document
+        .select(&base_href)
+        .next()
+        .and_then(|base| base.value().attr("href"))
+        .and_then(|href| base_url.join(href).ok())
+        .unwrap_or_else(|| base_url.clone());
+
+    let roots: Vec<ElementRef<'_>> = match scope {
+        Some(scope) => document.select(scope).collect(),
+        None => vec![document.root_element()],
+    };
+
+    let mut seen = HashSet::new();
+    let mut links = Vec::new();
+    for root in roots {
+        for anchor in root.select(&anchors) {
+            let href = anchor.value().attr("href").unwrap_or_default().trim();
+            if href.is_empty() || href.starts_with('#') {
+                continue;
+            }
+            let Ok(mut url) = base_url.join(href) else {
+                continue;
+            };
+            if !matches!(url.scheme(), "http" | "https") {
+                continue;
+            }
+            url.set_fragment(None);
+            if seen.insert(url.to_string()) {
+                let mut text = String::new();
+                collect_text(anchor, &mut text);
+                links.push(Link {
+                    url: url.into(),
+                    text: collapse_whitespace(&text).replace('\n', " "),
+                });
+            }
+        }
+    }
+    links
+}
+
+/// Link-preview metadata of a page. Tags missing from the page are left out.
+#[derive(Serialize, Default)]
+pub struct PageMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub og_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub og_description: Option<String>,
+    // Every og:image on the page, in document order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub og_images: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitter_card: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitter_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitter_description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitter_image: Option<String>,
+}
+
+/// Extracts the title, meta description, canonical URL and OpenGraph and
+/// Twitter card fields from `html`. URLs are resolved against `base_url`.
+pub fn extract_metadata(html: &str, base_url: &Url) -> PageMetadata {
+    let document = Html::parse_document(html);
+    let title = Selector::parse("title").expect("static selector is valid");
+    let canonical = Selector::parse("link[rel~=canonical][href]").expect("static selector is valid");
+    let meta = Selector::parse("meta[content]").expect("static selector is valid");
+
+    let resolve = |href: &str| {
+        base_url
+            .join(href.trim())
+            .map(String::from)
+            .unwrap_or_else(|_| href.trim().to_string())
+    };
+
+    let mut metadata = PageMetadata {
+        title: document
+            .select(&title)
+            .next()
+            .map(|title| collapse_whitespace(&title.text().collect::<String>()).replace('\n', " "))
+            .filter(|title| !title.is_empty()),
+        canonical_url: document
+            .select(&canonical)
+            .next()
+            .and_then(|link| link.value().attr("href"))
+            .map(resolve),
+        ..Default::default()
+    };
+
+    // OpenGraph uses `property`, Twitter cards and description use `name`,
+    // but pages mix them up often enough that both are accepted
+    for tag in document.select(&meta) {
+        let element = tag.value();
+        let Some(key) = element.attr("property").or_else(|| element.attr("name")) else {
+            continue;
+        };
+        let value = element.attr("content").unwrap_or_default().trim().to_string();
+        if value.is_empty() {
+            continue;
+        }
+        let field = match key.trim().to_ascii_lowercase().as_str() {
+            "description" => &mut metadata.description,
+            "og:title" => &mut metadata.og_title,
+            "og:description" => &mut metadata.og_description,
+            "og:image" | "og:image:url" => {
+                metadata.og_images.push(resolve(&value));
+                continue;
+            }
+            "twitter:card" => &mut metadata.twitter_card,
+            "twitter:title" => &mut metadata.twitter_title,
+            "twitter:description" => &mut metadata.twitter_description,
+            "twitter:image" => {
+                metadata.twitter_image.get_or_insert_with(|| resolve(&value));
+                continue;
+            }
+            _ => continue,
+        };
+        // The first occurrence of a tag wins
+        field.get_or_insert(value);
+    }
+
+    metadata
+}
+
+/// Finds the target of a `<meta http-equiv="refresh" content="5; url=...">`
+/// tag in `html`, resolved against `base_url`. Refreshes without a URL, which
+/// just reload the page, and targets other than http(s) are ignored.
+pub fn meta_refresh_target(html: &str, base_url: &Url) -> Option<Url> {
+    let document = Html::parse_document(html);
+    let refresh = Selector::parse("meta[http-equiv][content]").expect("static selector is valid");
+    let content = document
+        .select(&refresh)
+        .find(|tag| {
+            tag.value()
+                .attr("http-equiv")
+                .is_some_and(|equiv| equiv.trim().eq_ignore_ascii_case("refresh"))
+        })?
+        .value()
+        .attr("content")?;
+
+    // The delay comes first, then the URL, optionally prefixed with `url=`
+    let (_, target) = content.split_once([';', ','])?;
+    let target = target.trim_start();
+    let target = match target.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url") => target[3..].trim_start().strip_prefix('=')?,
+        _ => target,
+    };
+    let target = target.trim().trim_matches(['"', '\'']).trim();
+    if target.is_empty() {
+        return None;
+    }
+    let mut url = base_url.join(target).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    url.set_fragment(None);
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_to_text_drops_scripts_and_keeps_visible_text() {
+        let html = r#"<!DOCTYPE html>
+            <html>
+              <head><title>Ignored</title><style>p { color: red }</style></head>
+              <body>
+                <script>var hidden = "<p>not text</p>";</script>
+                <h1>Heading</h1>
+                <p>First   <b>bold <i>nested</i></b>
+                   paragraph.</p>
+                <div><p>Second</p><noscript>Enable JavaScript</noscript></div>
+              </body>
+            </html>"#;
+        assert_eq!(html_to_text(html), "Heading\nFirst bold nested paragraph.\nSecond");
+    }
+}
@@ -0,0 +1,167 @@
+// jobs.rs
+//
+// Asynchronous scrape jobs. A submitted job waits in a queue until one of the
+// background workers takes it, and its results accumulate here as they come
+// in so callers can poll for them. Finished jobs are forgotten after a TTL.
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Where a job is in its lifecycle.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Done | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+struct Job<R> {
+    status: JobStatus,
+    // When the job stopped running, for TTL-based cleanup
+    finished_at: Option<Instant>,
+    // One slot per URL, filled in as results arrive
+    results: Vec<Option<R>>,
+    // Why the job failed as a whole
+    error: Option<String>,
+}
+
+/// A job's state as reported by `GET /jobs/{id}`.
+#[derive(Serialize)]
+pub struct JobSnapshot<R> {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub total: usize,
+    pub completed: usize,
+    // Results of the URLs finished so far, in the order they were submitted
+    pub results: Vec<R>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Queue and state of all jobs, shared through `web::Data`. `T` is what a
+/// worker needs to run a job, `R` the result of one of its URLs.
+pub struct JobStore<T, R> {
+    ttl: Duration,
+    jobs: Mutex<HashMap<String, Job<R>>>,
+    sender: mpsc::UnboundedSender<(String, T)>,
+    receiver: tokio::sync::Mutex<mpsc::UnboundedReceiver<(String, T)>>,
+}
+
+impl<T, R: Clone> JobStore<T, R> {
+    /// Creates an empty store keeping finished jobs for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        JobStore {
+            ttl,
+            jobs: Mutex::new(HashMap::new()),
+            sender,
+            receiver: tokio::sync::Mutex::new(receiver),
+        }
+    }
+
+    /// Queues a job for `total` URLs and returns its id, dropping expired
+    /// jobs on the way.
+    pub fn submit(&self, input: T, total: usize) -> String {
+        let id = Uuid::new_v4().to_string();
+        let mut jobs = self.jobs.lock().expect("job store lock poisoned");
+        jobs.retain(|_, job| job.finished_at.is_none_or(|at| at.elapsed() < self.ttl));
+        jobs.insert(
+            id.clone(),
+            Job {
+                status: JobStatus::Queued,
+                finished_at: None,
+                results: (0..total).map(|_| None).collect(),
+                error: None,
+            },
+        );
+        // The receiver lives as long as the store, so sending can't fail
+        let _ = self.sender.send((id.clone(), input));
+        id
+    }
+
+    /// Waits for the next queued job and marks it running. Jobs cancelled
+    /// while queued are skipped.
+    pub async fn next(&self) -> (String, T) {
+        let mut receiver = self.receiver.lock().await;
+        loop {
+            let (id, input) = receiver.recv().await.expect("the store holds a sender");
+            let mut jobs = self.jobs.lock().expect("job store lock poisoned");
+            if let Some(job) = jobs.get_mut(&id).filter(|job| job.status == JobStatus::Queued) {
+                job.status = JobStatus::Running;
+                return (id, input);
+            }
+        }
+    }
+
+    /// Whether the job was cancelled, or expired, so its worker should stop.
+    pub fn is_cancelled(&self, id: &str) -> bool {
+        let jobs = self.jobs.lock().expect("job store lock poisoned");
+        jobs.get(id).is_none_or(|job| job.status == JobStatus::Cancelled)
+    }
+
+    /// Stores the result for the URL at `index`.
+    pub fn record(&self, id: &str, index: usize, result: R) {
+        let mut jobs = self.jobs.lock().expect("job store lock poisoned");
+        if let Some(slot) = jobs.get_mut(id).and_then(|job| job.results.get_mut(index)) {
+            *slot = Some(result);
+        }
+    }
+
+    /// Marks a running job done, or failed with `error`. A job cancelled in
+    /// the meantime stays cancelled.
+    pub fn finish(&self, id: &str, error: Option<String>) {
+        let mut jobs = self.jobs.lock().expect("job store lock poisoned");
+        if let Some(job) = jobs.get_mut(id).filter(|job| job.status == JobStatus::Running) {
+            job.status = match error {
+                Some(_) => JobStatus::Failed,
+                None => JobStatus::Done,
+            };
+            job.error = error;
+            job.finished_at = Some(Instant::now());
+        }
+    }
+
+    /// Cancels a job that hasn't finished yet; URLs already being fetched
+    /// still complete. Returns its state afterwards, or `None` if it's unknown.
+    pub fn cancel(&self, id: &str) -> Option<JobSnapshot<R>> {
+        {
+            let mut jobs = self.jobs.lock().expect("job store lock poisoned");
+            let job = jobs.get_mut(id)?;
+            if !job.status.is_finished() {
+                job.status = JobStatus::Cancelled;
+                job.finished_at = Some(Instant::now());
+            }
+        }
+        self.snapshot(id)
+    }
+
+    /// Returns the job's progress and the results so far, or `None` if it's
+    /// unknown or has expired.
+    pub fn snapshot(&self, id: &str) -> Option<JobSnapshot<R>> {
+        let jobs = self.jobs.lock().expect("job store lock poisoned");
+        let job = jobs
+            .get(id)
+            .filter(|job| job.finished_at.is_none_or(|at| at.elapsed() < self.ttl))?;
+        let results: Vec<R> = job.results.iter().flatten().cloned().collect();
+        Some(JobSnapshot {
+            job_id: id.to_string(),
+            status: job.status,
+            total: job.results.len(),
+            completed: results.len(),
+            results,
+            error: job.error.clone(),
+        })
+    }
+}
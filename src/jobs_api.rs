@@ -0,0 +1,148 @@
+// jobs_api.rs
+//
+// The /jobs endpoints: batches submitted to run in the background, then polled
+// or cancelled by id. The workers spawned at startup take the queued jobs one
+// at a time and fetch their URLs as a batch would.
+use crate::{error_body, request_id, with_request_id, FetchOptions, ScrapeError};
+use crate::batch_api::{batch_client, scrape_batch_url, BatchScrapeRequest, ScrapeResult};
+use crate::circuit_breaker::CircuitBreakers;
+use crate::config::Config;
+use crate::jobs::{JobStatus, JobStore};
+use crate::politeness::HostThrottle;
+use crate::proxy_pool::ProxyPool;
+use crate::robots::RobotsCache;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use futures::future;
+use reqwest::Client;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{field, info, info_span, warn, Instrument};
+
+// Body returned when a job is submitted
+#[derive(Serialize)]
+struct JobSubmitted {
+    job_id: String,
+    status: JobStatus,
+}
+
+// Queue and state of the jobs submitted through /jobs
+pub type Jobs = JobStore<BatchScrapeRequest, ScrapeResult>;
+
+/// Handles the POST request to submit a batch as a background job.
+///
+/// The caller gets a `job_id` right away to poll with. The proxy settings
+/// are only checked once a worker picks the job up, failing it if they're invalid.
+pub async fn submit_job_handler(
+    http_req: HttpRequest,
+    req: web::Json<BatchScrapeRequest>,
+    jobs: web::Data<Jobs>,
+) -> impl Responder {
+    let request_id = request_id(&http_req);
+    let req = req.into_inner();
+    let urls = req.urls.len();
+    let job_id = jobs.submit(req, urls);
+    info!(request_id = %request_id, job_id = %job_id, urls, "Queued job");
+    let response = HttpResponse::Accepted().json(JobSubmitted {
+        job_id,
+        status: JobStatus::Queued,
+    });
+    with_request_id(response, &request_id)
+}
+
+/// Handles the GET request reporting a job's status and results so far.
+pub async fn job_status_handler(job_id: web::Path<String>, jobs: web::Data<Jobs>) -> impl Responder {
+    match jobs.snapshot(&job_id) {
+        Some(snapshot) => HttpResponse::Ok().json(snapshot),
+        None => job_not_found(),
+    }
+}
+
+/// Handles the DELETE request cancelling a job. Finished jobs are left as they are.
+pub async fn cancel_job_handler(job_id: web::Path<String>, jobs: web::Data<Jobs>) -> impl Responder {
+    match jobs.cancel(&job_id) {
+        Some(snapshot) => {
+            info!(job_id = %job_id, status = ?snapshot.status, "Cancelled job");
+            HttpResponse::Ok().json(snapshot)
+        }
+        None => job_not_found(),
+    }
+}
+
+fn job_not_found() -> HttpResponse {
+    let e = ScrapeError::JobNotFound;
+    HttpResponse::build(e.status_code()).json(error_body(&e))
+}
+
+/// Takes jobs from the queue one at a time and runs them until the process exits.
+pub async fn job_worker(
+    jobs: web::Data<Jobs>,
+    config: web::Data<Config>,
+    base_client: web::Data<Client>,
+    proxy_pool: web::Data<ProxyPool>,
+    semaphore: web::Data<Semaphore>,
+    robots_cache: web::Data<RobotsCache>,
+    throttle: web::Data<HostThrottle>,
+    breakers: web::Data<CircuitBreakers>,
+) {
+    loop {
+        let (job_id, req) = jobs.next().await;
+        let span = info_span!("job", job_id = %job_id, urls = req.urls.len(), proxy = field::Empty);
+        let run = run_job(
+            &jobs,
+            &job_id,
+            &req,
+            &config,
+            &throttle,
+            &base_client,
+            &proxy_pool,
+            &semaphore,
+            &robots_cache,
+            &breakers,
+        );
+        let error = run.instrument(span.clone()).await.err();
+        jobs.finish(&job_id, error.as_ref().map(ToString::to_string));
+        span.in_scope(|| match &error {
+            Some(e) => warn!(error = %e, "Job failed"),
+            None => info!("Job finished"),
+        });
+    }
+}
+
+/// Scrapes the URLs of a job like a batch, sharing the batch concurrency
+/// limit, and records each result as it completes. URLs not yet started when
+/// the job is cancelled are skipped.
+async fn run_job(
+    jobs: &Jobs,
+    job_id: &str,
+    req: &BatchScrapeRequest,
+    config: &Config,
+    throttle: &HostThrottle,
+    base_client: &Client,
+    proxy_pool: &Arc<ProxyPool>,
+    semaphore: &Semaphore,
+    robots_cache: &RobotsCache,
+    breakers: &Arc<CircuitBreakers>,
+) -> Result<(), ScrapeError> {
+    let client = batch_client(req, config, base_client, proxy_pool, breakers)?;
+    info!("Starting job");
+
+    let options = FetchOptions::for_batch(config, req);
+    let respect_robots = req.respect_robots.unwrap_or(config.respect_robots);
+    future::join_all(req.urls.iter().enumerate().map(|(index, url)| {
+        let url_span = info_span!("scrape", url = %url, status = field::Empty);
+        let client = &client;
+        let options = &options;
+        async move {
+            let _permit = semaphore.acquire().await.expect("semaphore is never closed");
+            if jobs.is_cancelled(job_id) {
+                return;
+            }
+            let result = scrape_batch_url(config, throttle, client, robots_cache, url, options, respect_robots).await;
+            jobs.record(job_id, index, result);
+        }
+        .instrument(url_span)
+    }))
+    .await;
+    Ok(())
+}
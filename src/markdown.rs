@@ -0,0 +1,343 @@
+// markdown.rs
+//
+// HTML to Markdown conversion for the "markdown" output mode, aimed at
+// feeding pages to language models: headings, lists, links, images, emphasis,
+// quotes, tables and code blocks are kept, everything else is reduced to its
+// text. Navigation boilerplate is dropped: only the page's <main> or
+// <article> is converted when it has one, and menus, sidebars, footers and
+// forms are skipped wherever they are.
+use scraper::{ElementRef, Html, Selector};
+use url::Url;
+
+// Elements dropped with their contents wherever they appear
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "template", "head", "nav", "aside", "footer", "form", "button",
+    "iframe", "svg", "canvas", "select", "dialog",
+];
+
+// Elements that stand on their own lines but need no markup of their own
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "details", "dd", "div", "dl", "dt", "figcaption", "figure", "header",
+    "main", "p", "section", "summary",
+];
+
+/// Converts the elements of `html` matching `selector` to Markdown, one entry
+/// per element in document order, as `html_to_markdown` would.
+pub fn select_markdown(html: &str, selector: &Selector, base_url: &Url) -> Vec<String> {
+    let converter = Converter {
+        base_url,
+        skip_header: false,
+    };
+    Html::parse_document(html)
+        .select(selector)
+        .map(|element| {
+            let mut out = String::new();
+            converter.element(element, &mut out);
+            tidy(&out)
+        })
+        .collect()
+}
+
+/// Converts an HTML document to Markdown. Links and images are made absolute
+/// against `base_url`; the result has no leading, trailing or repeated blank lines.
+pub fn html_to_markdown(html: &str, base_url: &Url) -> String {
+    let document = Html::parse_document(html);
+    let content_root = ["main", "article", "body"].iter().find_map(|name| {
+        let selector = Selector::parse(name).expect("static selector is valid");
+        document.select(&selector).next()
+    });
+    // A page without <main> or <article> has its site header around the content
+    let skip_header = content_root.is_none_or(|root| root.value().name() == "body");
+    let converter = Converter { base_url, skip_header };
+    let mut out = String::new();
+    converter.children(content_root.unwrap_or_else(|| document.root_element()), &mut out);
+    tidy(&out)
+}
+
+struct Converter<'a> {
+    base_url: &'a Url,
+    skip_header: bool,
+}
+
+impl Converter<'_> {
+    fn children(&self, element: ElementRef<'_>, out: &mut String) {
+        for child in element.children() {
+            if let Some(text) = child.value().as_text() {
+                push_inline(out, &collapse(text));
+            } else if let Some(child) = ElementRef::wrap(child) {
+                self.element(child, out);
+            }
+        }
+    }
+
+    fn element(&self, element: ElementRef<'_>, out: &mut String) {
+        let name = element.value().name();
+        if SKIPPED_ELEMENTS.contains(&name) || (name == "header" && self.skip_header) {
+            return;
+        }
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = usize::from(name.as_bytes()[1] - b'0');
+                let text = one_line(&self.render(element));
+                if !text.is_empty() {
+                    push_block(out, &format!("{} {}", "#".repeat(level), text));
+                }
+            }
+            "br" => out.push('\n'),
+            "hr" => push_block(out, "---"),
+            "pre" => {
+                let code: String = element.text().collect();
+                let fence = if code.contains("```") { "~~~" } else { "```" };
+                let language = code_language(element).unwrap_or_default();
+                push_block(out, &format!("{}{}\n{}\n{}", fence, language, code.trim_end_matches('\n'), fence));
+            }
+            "blockquote" => {
+                let quoted = tidy(&self.render(element));
+                if !quoted.is_empty() {
+                    let lines: Vec<String> = quoted
+                        .lines()
+                        .map(|line| format!("> {}", line).trim_end().to_string())
+                        .collect();
+                    push_block(out, &lines.join("\n"));
+                }
+            }
+            "ul" | "ol" => {
+                let items = self.list(element, name == "ol");
+                if !items.is_empty() {
+                    push_block(out, &items);
+                }
+            }
+            "table" => {
+                let table = self.table(element);
+                if !table.is_empty() {
+                    push_block(out, &table);
+                }
+            }
+            "a" => {
+                let text = one_line(&self.render(element));
+                match element.value().attr("href").and_then(|href| self.resolve(href)) {
+                    Some(href) if !text.is_empty() => push_inline(out, &format!("[{}]({})", text, href)),
+                    _ => push_inline(out, &text),
+                }
+            }
+            "img" => {
+                if let Some(src) = element.value().attr("src").and_then(|src| self.resolve(src)) {
+                    let alt = one_line(element.value().attr("alt").unwrap_or_default());
+                    push_inline(out, &format!("![{}]({})", alt, src));
+                }
+            }
+            "strong" | "b" => self.wrapped(element, "**", out),
+            "em" | "i" => self.wrapped(element, "*", out),
+            "del" | "s" => self.wrapped(element, "~~", out),
+            "code" => {
+                let code = one_line(&element.text().collect::<String>());
+                // Double backticks, padded with spaces, let the code contain a backtick
+                if code.contains('`') {
+                    push_inline(out, &format!("`` {} ``", code));
+                } else if !code.is_empty() {
+                    push_inline(out, &format!("`{}`", code));
+                }
+            }
+            _ if BLOCK_ELEMENTS.contains(&name) => {
+                let block = tidy(&self.render(element));
+                if !block.is_empty() {
+                    push_block(out, &block);
+                }
+            }
+            _ => self.children(element, out),
+        }
+    }
+
+    /// Converts an element's children on their own.
+    fn render(&self, element: ElementRef<'_>) -> String {
+        let mut out = String::new();
+        self.children(element, &mut out);
+        out
+    }
+
+    /// Inline content between a pair of markers, like `**bold**`.
+    fn wrapped(&self, element: ElementRef<'_>, marker: &str, out: &mut String) {
+        let text = one_line(&self.render(element));
+        if !text.is_empty() {
+            push_inline(out, &format!("{}{}{}", marker, text, marker));
+        }
+    }
+
+    /// The items of a list, one per line, with nested blocks indented under their item.
+    fn list(&self, element: ElementRef<'_>, ordered: bool) -> String {
+        let start: usize = element.value().attr("start").and_then(|start| start.parse().ok()).unwrap_or(1);
+        let mut lines = Vec::new();
+        let items = element.children().filter_map(ElementRef::wrap).filter(|child| child.value().name() == "li");
+        for (index, item) in items.enumerate() {
+            let marker = if ordered { format!("{}. ", start + index) } else { "- ".to_string() };
+            let indent = " ".repeat(marker.len());
+            let content = tidy(&self.render(item));
+            // Blank lines would make a loose list, spread out for no benefit
+            for (line_index, line) in content.lines().filter(|line| !line.is_empty()).enumerate() {
+                let prefix = if line_index == 0 { marker.as_str() } else { indent.as_str() };
+                lines.push(format!("{}{}", prefix, line));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// A table as a pipe table, with its first row as the header.
+    fn table(&self, element: ElementRef<'_>) -> String {
+        let row_selector = Selector::parse("tr").expect("static selector is valid");
+        let rows: Vec<Vec<String>> = element
+            .select(&row_selector)
+            .map(|row| {
+                row.children()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                    .map(|cell| one_line(&self.render(cell)).replace('|', "\\|"))
+                    .collect()
+            })
+            .filter(|row: &Vec<String>| !row.is_empty())
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut lines = Vec::new();
+        for (index, row) in rows.iter().enumerate() {
+            let mut cells = row.clone();
+            cells.resize(columns, String::new());
+            lines.push(format!("| {} |", cells.join(" | ")));
+            if index == 0 {
+                lines.push(format!("|{}", " --- |".repeat(columns)));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Resolves a link or image target, keeping only http(s) URLs.
+    fn resolve(&self, target: &str) -> Option<Url> {
+        let url = self.base_url.join(target.trim()).ok()?;
+        matches!(url.scheme(), "http" | "https").then_some(url)
+    }
+}
+
+/// The language of a code block, from a `language-*` or `lang-*` class on
+/// the <pre> or the <code> inside it.
+fn code_language(pre: ElementRef<'_>) -> Option<String> {
+    let code = pre.children().filter_map(ElementRef::wrap).find(|child| child.value().name() == "code");
+    [Some(pre), code].into_iter().flatten().find_map(|element| {
+        element.value().classes().find_map(|class| {
+            class
+                .strip_prefix("language-")
+                .or_else(|| class.strip_prefix("lang-"))
+                .map(String::from)
+        })
+    })
+}
+
+/// Collapses runs of whitespace in a text node to single spaces, keeping a
+/// leading or trailing one so neighbouring inline content stays apart.
+fn collapse(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() {
+        return if text.is_empty() { String::new() } else { " ".to_string() };
+    }
+    let mut collapsed = String::new();
+    if text.starts_with(char::is_whitespace) {
+        collapsed.push(' ');
+    }
+    collapsed.push_str(&words.join(" "));
+    if text.ends_with(char::is_whitespace) {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+/// Joins converted content onto a single line.
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Appends inline content, dropping a space at the start of a line or after another space.
+fn push_inline(out: &mut String, text: &str) {
+    let text = if out.is_empty() || out.ends_with('\n') || out.ends_with(' ') {
+        text.trim_start()
+    } else {
+        text
+    };
+    out.push_str(text);
+}
+
+/// Appends a block, separated from what comes before and after by a blank line.
+fn push_block(out: &mut String, block: &str) {
+    out.push_str("\n\n");
+    out.push_str(block);
+    out.push_str("\n\n");
+}
+
+/// Trims trailing spaces off every line, collapses runs of blank lines into
+/// one and drops blank lines at either end, leaving code blocks as they are.
+fn tidy(markdown: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut fence: Option<&str> = None;
+    for line in markdown.lines() {
+        let marker = line.trim_start();
+        if let Some(open) = fence {
+            if marker == open {
+                fence = None;
+            }
+            lines.push(line);
+            continue;
+        }
+        if let Some(open) = ["```", "~~~"].into_iter().find(|open| marker.starts_with(open)) {
+            fence = Some(open);
+        }
+        let line = line.trim_end();
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn representative_document_converts_to_markdown() {
+        let html = r#"<html><body>
+            <header><a href="/">Site</a></header>
+            <nav><ul><li><a href="/a">Menu</a></li></ul></nav>
+            <main>
+              <h1>Title</h1>
+              <p>Some <strong>bold</strong> and <em>emphasis</em> with a <a href="/docs?x=1">link</a>
+                 and <code>inline</code> code.</p>
+              <ul><li>One</li><li>Two<ol start="3"><li>Nested</li></ol></li></ul>
+              <pre><code class="language-rust">fn main() {
+    println!("hi");
+}</code></pre>
+              <blockquote><p>Quoted</p></blockquote>
+              <table><tr><th>A</th><th>B</th></tr><tr><td>1</td><td>2|3</td></tr></table>
+              <img src="pic.png" alt="A picture">
+            </main>
+            <footer>Copyright</footer>
+            <script>alert(1)</script>
+        </body></html>"#;
+        let base_url = Url::parse("https://example.com/page/").unwrap();
+        let expected = "# Title\n\n\
+            Some **bold** and *emphasis* with a [link](https://example.com/docs?x=1) and `inline` code.\n\n\
+            - One\n\
+            - Two\n  3. Nested\n\n\
+            ```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\n\
+            > Quoted\n\n\
+            | A | B |\n| --- | --- |\n| 1 | 2\\|3 |\n\n\
+            ![A picture](https://example.com/page/pic.png)";
+        assert_eq!(html_to_markdown(html, &base_url), expected);
+    }
+
+    #[test]
+    fn page_without_main_drops_its_header() {
+        let html = "<body><header>Site name</header><h2>Heading</h2><p>Text</p></body>";
+        let base_url = Url::parse("https://example.com/").unwrap();
+        assert_eq!(html_to_markdown(html, &base_url), "## Heading\n\nText");
+    }
+}
@@ -0,0 +1,128 @@
+// metrics.rs
+use crate::circuit_breaker::{CircuitBreakers, CircuitState};
+use crate::redact;
+use actix_web::{http::StatusCode, web, HttpResponse, Responder};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::time::Duration;
+
+/// Process-wide Prometheus metrics, shared between workers through `web::Data`.
+pub struct Metrics {
+    registry: Registry,
+    scrapes_total: IntCounter,
+    scrape_successes_total: IntCounter,
+    scrape_failures_total: IntCounterVec,
+    scrape_duration_seconds: HistogramVec,
+    proxy_circuit_state: IntGaugeVec,
+}
+
+impl Metrics {
+    /// Creates the metrics and registers them with a fresh registry.
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new();
+
+        let scrapes_total = IntCounter::new("scrapes_total", "Total number of scrape requests")?;
+        let scrape_successes_total =
+            IntCounter::new("scrape_successes_total", "Number of scrapes that returned content")?;
+        // Labeled by the status class ("4xx", "5xx", ...) returned to the caller
+        let scrape_failures_total = IntCounterVec::new(
+            Opts::new("scrape_failures_total", "Number of failed scrapes by status class"),
+            &["status_class"],
+        )?;
+        // Labeled by "success" or "failure"
+        let scrape_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "scrape_duration_seconds",
+                "Duration of the outbound scrape request in seconds",
+            ),
+            &["outcome"],
+        )?;
+        // Labeled by proxy address, with credentials masked
+        let proxy_circuit_state = IntGaugeVec::new(
+            Opts::new(
+                "proxy_circuit_state",
+                "Circuit breaker state per proxy: 0 closed, 1 open, 2 half-open",
+            ),
+            &["proxy"],
+        )?;
+
+        registry.register(Box::new(scrapes_total.clone()))?;
+        registry.register(Box::new(scrape_successes_total.clone()))?;
+        registry.register(Box::new(scrape_failures_total.clone()))?;
+        registry.register(Box::new(scrape_duration_seconds.clone()))?;
+        registry.register(Box::new(proxy_circuit_state.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            scrapes_total,
+            scrape_successes_total,
+            scrape_failures_total,
+            scrape_duration_seconds,
+            proxy_circuit_state,
+        })
+    }
+
+    /// Counts an incoming scrape request.
+    pub fn record_scrape(&self) {
+        self.scrapes_total.inc();
+    }
+
+    /// Counts a successful scrape.
+    pub fn record_success(&self) {
+        self.scrape_successes_total.inc();
+    }
+
+    /// Counts a failed scrape by the class of the status returned to the caller.
+    pub fn record_failure(&self, status: StatusCode) {
+        let status_class = format!("{}xx", status.as_u16() / 100);
+        self.scrape_failures_total
+            .with_label_values(&[status_class.as_str()])
+            .inc();
+    }
+
+    /// Records how long the outbound request took.
+    pub fn observe_duration(&self, success: bool, duration: Duration) {
+        let outcome = if success { "success" } else { "failure" };
+        self.scrape_duration_seconds
+            .with_label_values(&[outcome])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Replaces the reported circuit breaker states with `states`.
+    pub fn set_circuit_states(&self, states: &[(String, CircuitState)]) {
+        self.proxy_circuit_state.reset();
+        for (proxy, state) in states {
+            self.proxy_circuit_state
+                .with_label_values(&[redact::proxy_url(proxy).as_str()])
+                .set(state.gauge_value());
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    fn render(&self) -> Result<String, String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(buffer).map_err(|e| e.to_string())
+    }
+}
+
+/// Serves the metrics for Prometheus to scrape.
+pub async fn metrics_handler(
+    metrics: web::Data<Metrics>,
+    breakers: web::Data<CircuitBreakers>,
+) -> impl Responder {
+    metrics.set_circuit_states(&breakers.states());
+    match metrics.render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(TextEncoder::new().format_type())
+            .body(body),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to encode metrics");
+            HttpResponse::InternalServerError().body(format!("Failed to encode metrics: {}", e))
+        }
+    }
+}
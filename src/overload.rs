@@ -0,0 +1,63 @@
+// overload.rs
+//
+// Load shedding. At most MAX_IN_FLIGHT_REQUESTS scraping requests (single
+// scrapes, batches, crawls, downloads and the like) are handled at once across
+// the process; past that, new ones get an immediate 503 with Retry-After
+// rather than waiting in memory until a traffic spike takes the process down.
+// This is separate from MAX_CONCURRENCY, which limits the fetches within
+// batches.
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+// Seconds an overloaded caller is asked to wait before trying again
+const RETRY_AFTER_SECONDS: u64 = 1;
+
+/// Slots for requests being handled, shared through `web::Data`.
+pub struct Admission {
+    // One permit per request allowed in flight; `None` admits everything
+    slots: Option<Semaphore>,
+}
+
+impl Admission {
+    pub fn new(limit: Option<usize>) -> Self {
+        Admission {
+            slots: limit.map(Semaphore::new),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OverloadedResponse {
+    error: &'static str,
+}
+
+/// Middleware holding a slot for the request while it's handled, answering
+/// 503 at once when none is free. A streamed response gives its slot back
+/// when the handler returns, before the stream ends.
+pub async fn shed_when_saturated(
+    req: ServiceRequest,
+    next: Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let Some(admission) = req.app_data::<web::Data<Admission>>().cloned() else {
+        return next.call(req).await;
+    };
+    let Some(slots) = &admission.slots else {
+        return next.call(req).await;
+    };
+    let Ok(_slot) = slots.try_acquire() else {
+        warn!(path = req.path(), "Rejecting request, too many in flight");
+        let response = HttpResponse::ServiceUnavailable()
+            .insert_header((RETRY_AFTER, RETRY_AFTER_SECONDS.to_string()))
+            .json(OverloadedResponse {
+                error: "Too many requests in flight, try again shortly",
+            });
+        return Ok(req.into_response(response));
+    };
+    next.call(req).await
+}
@@ -0,0 +1,83 @@
+// politeness.rs
+//
+// Per-host request spacing. With PER_HOST_DELAY_MS set, requests to the same
+// host are at least that far apart across the whole process, whichever
+// handler, batch or job they come from. Requests to different hosts don't
+// wait on each other.
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// When each host may next be contacted, shared through `web::Data`.
+pub struct HostThrottle {
+    delay: Duration,
+    // Time slot most recently handed out per host
+    slots: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostThrottle {
+    /// Spaces requests to a host `delay` apart; a zero delay disables it.
+    pub fn new(delay: Duration) -> Self {
+        HostThrottle {
+            delay,
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until a request to the host of `url` is due. Each caller
+    /// reserves the next free slot before sleeping, so concurrent requests to
+    /// one host queue up behind each other instead of all firing at once.
+    pub async fn wait(&self, url: &str) {
+        if self.delay.is_zero() {
+            return;
+        }
+        let Some(host) = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_ascii_lowercase)) else {
+            return;
+        };
+
+        let slot = {
+            let mut slots = self.slots.lock().expect("host throttle lock poisoned");
+            let now = Instant::now();
+            // Hosts last contacted longer ago than the delay don't hold anyone up
+            slots.retain(|_, slot| now.duration_since(*slot) < self.delay);
+            let slot = slots.get(&host).map_or(now, |last| (*last + self.delay).max(now));
+            slots.insert(host, slot);
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELAY: Duration = Duration::from_millis(200);
+
+    #[tokio::test]
+    async fn same_host_is_spaced_and_other_hosts_are_not() {
+        let throttle = HostThrottle::new(DELAY);
+        let started = Instant::now();
+        throttle.wait("http://example.com/a").await;
+        throttle.wait("http://other.example/").await;
+        assert!(started.elapsed() < DELAY / 2);
+        throttle.wait("http://EXAMPLE.com:8080/b").await;
+        assert!(started.elapsed() >= DELAY);
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_queue_up() {
+        let throttle = HostThrottle::new(DELAY);
+        let started = Instant::now();
+        let waits = (0..3).map(|_| async {
+            throttle.wait("http://example.com/").await;
+            started.elapsed()
+        });
+        let mut waited = futures::future::join_all(waits).await;
+        waited.sort();
+        assert!(waited[0] < DELAY / 2);
+        assert!(waited[1] >= DELAY && waited[1] < DELAY * 2);
+        assert!(waited[2] >= DELAY * 2);
+    }
+}
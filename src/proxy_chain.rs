@@ -0,0 +1,221 @@
+// proxy_chain.rs
+//
+// Chaining through UPSTREAM_PROXY, an HTTP proxy that has to be crossed to
+// reach the scrape proxy, like a corporate proxy in front of Tor. reqwest
+// takes a single proxy, so each scrape proxy gets a local forwarder instead: a
+// listener on 127.0.0.1 whose connections are tunnelled with CONNECT through
+// the upstream proxy to the scrape proxy. Clients are pointed at the forwarder
+// in place of the scrape proxy, with the same scheme and credentials, so
+// SOCKS5 and plain HTTP scrape proxies chain alike. An https:// scrape proxy
+// can't: its certificate wouldn't match the forwarder's address.
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+use url::Url;
+
+// Most forwarders kept at once; each scrape proxy needs one for good
+const MAX_FORWARDERS: usize = 256;
+// How long the upstream proxy may take to open a tunnel
+const TUNNEL_TIMEOUT: Duration = Duration::from_secs(30);
+// Longest CONNECT answer read from the upstream proxy
+const MAX_CONNECT_RESPONSE_BYTES: usize = 16 * 1024;
+
+/// Why a scrape proxy can't be reached through UPSTREAM_PROXY.
+#[derive(Debug)]
+pub enum ChainError {
+    // The scrape proxy's scheme can't go through a tunnel; holds it
+    UnsupportedScheme(String),
+    // The scrape proxy URL has no host to tunnel to
+    NoHost,
+    // MAX_FORWARDERS scrape proxies already have a forwarder
+    TooManyProxies,
+    // No local port could be opened for the forwarder
+    Bind(io::Error),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::UnsupportedScheme(scheme) => write!(
+                f,
+                "a {}:// proxy can't be reached through a tunnel; expected socks5, socks5h or http",
+                scheme
+            ),
+            ChainError::NoHost => write!(f, "the proxy URL has no host"),
+            ChainError::TooManyProxies => {
+                write!(f, "already chaining the limit of {} distinct proxies", MAX_FORWARDERS)
+            }
+            ChainError::Bind(e) => write!(f, "can't open a local forwarder ({})", e),
+        }
+    }
+}
+
+// Where CONNECT requests go, and how they authenticate
+struct Upstream {
+    // host:port of the upstream proxy
+    addr: String,
+    // Proxy-Authorization value, from credentials in UPSTREAM_PROXY
+    authorization: Option<String>,
+}
+
+/// The upstream proxy and a forwarder for each scrape proxy chained behind
+/// it, shared through `Config`.
+pub struct ProxyChain {
+    upstream: Arc<Upstream>,
+    // UPSTREAM_PROXY as configured, for the readiness probe
+    url: String,
+    // Local port of the forwarder for each scrape proxy's host:port
+    forwarders: Mutex<HashMap<String, u16>>,
+}
+
+impl ProxyChain {
+    /// Parses UPSTREAM_PROXY, which must be an http:// proxy.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let url = Url::parse(value).map_err(|e| e.to_string())?;
+        if url.scheme() != "http" {
+            return Err(format!("expected an http:// proxy, got {}://", url.scheme()));
+        }
+        let host = url.host_str().ok_or("the proxy URL has no host")?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let authorization = (!url.username().is_empty()).then(|| {
+            let decode = |part: &str| percent_decode(part);
+            let credentials = format!("{}:{}", decode(url.username()), decode(url.password().unwrap_or_default()));
+            format!("Basic {}", BASE64.encode(credentials))
+        });
+        Ok(ProxyChain {
+            upstream: Arc::new(Upstream {
+                addr: format!("{}:{}", host, port),
+                authorization,
+            }),
+            url: value.to_string(),
+            forwarders: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// UPSTREAM_PROXY as configured.
+    pub fn upstream_url(&self) -> &str {
+        &self.url
+    }
+
+    /// Checks that a scrape proxy can be chained, without starting anything.
+    pub fn check(&self, proxy: &str) -> Result<(), ChainError> {
+        tunnel_target(&Url::parse(proxy).map_err(|_| ChainError::NoHost)?).map(|_| ())
+    }
+
+    /// Rewrites a scrape proxy URL to point at its forwarder, starting one
+    /// the first time the proxy is seen. Must be called within the runtime.
+    pub fn chain(&self, proxy: &str) -> Result<String, ChainError> {
+        let mut url = Url::parse(proxy).map_err(|_| ChainError::NoHost)?;
+        let target = tunnel_target(&url)?;
+        let mut forwarders = self.forwarders.lock().expect("proxy chain lock poisoned");
+        let port = match forwarders.get(&target) {
+            Some(port) => *port,
+            None => {
+                if forwarders.len() >= MAX_FORWARDERS {
+                    return Err(ChainError::TooManyProxies);
+                }
+                let port = self.start_forwarder(target.clone()).map_err(ChainError::Bind)?;
+                forwarders.insert(target, port);
+                port
+            }
+        };
+        url.set_host(Some("127.0.0.1")).expect("an IP address is a valid host");
+        url.set_port(Some(port)).expect("the URL has a host");
+        Ok(url.to_string())
+    }
+
+    fn start_forwarder(&self, target: String) -> io::Result<u16> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let listener = TcpListener::from_std(listener)?;
+        info!(port, upstream = %self.upstream.addr, "Forwarding a chained proxy through UPSTREAM_PROXY");
+        let upstream = self.upstream.clone();
+        tokio::spawn(async move {
+            loop {
+                let client = match listener.accept().await {
+                    Ok((client, _)) => client,
+                    Err(e) => {
+                        warn!(error = %e, "Chained proxy forwarder failed to accept a connection");
+                        continue;
+                    }
+                };
+                let upstream = upstream.clone();
+                let target = target.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = tunnel(client, &upstream, &target).await {
+                        warn!(upstream = %upstream.addr, error = %e, "Tunnel through UPSTREAM_PROXY failed");
+                    }
+                });
+            }
+        });
+        Ok(port)
+    }
+}
+
+/// The host:port a scrape proxy is reached at, if its scheme can be tunnelled.
+fn tunnel_target(url: &Url) -> Result<String, ChainError> {
+    let default_port = match url.scheme() {
+        "socks5" | "socks5h" => 1080,
+        "http" => 80,
+        scheme => return Err(ChainError::UnsupportedScheme(scheme.to_string())),
+    };
+    let host = url.host_str().ok_or(ChainError::NoHost)?;
+    Ok(format!("{}:{}", host, url.port().unwrap_or(default_port)))
+}
+
+/// Opens a CONNECT tunnel to `target` through the upstream proxy, then
+/// relays bytes both ways until either side closes.
+async fn tunnel(mut client: TcpStream, upstream: &Upstream, target: &str) -> io::Result<()> {
+    let mut server = tokio::time::timeout(TUNNEL_TIMEOUT, connect(upstream, target))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "upstream proxy didn't open the tunnel in time"))??;
+    tokio::io::copy_bidirectional(&mut client, &mut server).await?;
+    Ok(())
+}
+
+async fn connect(upstream: &Upstream, target: &str) -> io::Result<BufReader<TcpStream>> {
+    let mut server = BufReader::new(TcpStream::connect(&upstream.addr).await?);
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+    if let Some(authorization) = &upstream.authorization {
+        request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+    }
+    request.push_str("\r\n");
+    server.get_mut().write_all(request.as_bytes()).await?;
+
+    // The status line, then headers up to an empty line; nothing follows
+    // until the client speaks, so the buffer holds no tunnelled bytes
+    let mut status = String::new();
+    server.read_line(&mut status).await?;
+    let mut read = status.len();
+    loop {
+        let mut line = String::new();
+        let length = server.read_line(&mut line).await?;
+        read += length;
+        if length == 0 || read > MAX_CONNECT_RESPONSE_BYTES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed CONNECT response"));
+        }
+        if line.trim_end().is_empty() {
+            break;
+        }
+    }
+    let code = status.split_whitespace().nth(1);
+    if code != Some("200") {
+        let status = status.trim_end().to_string();
+        return Err(io::Error::other(format!("CONNECT to {} refused: {}", target, status)));
+    }
+    Ok(server)
+}
+
+fn percent_decode(part: &str) -> String {
+    url::form_urlencoded::parse(format!("x={}", part.replace('+', "%2B")).as_bytes())
+        .next()
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,215 @@
+// proxy_pool.rs
+use rand::Rng;
+use reqwest::Client;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A proxy listed in `PROXY_POOL`, as `addr` or `addr|weight`.
+pub struct PoolProxy {
+    pub addr: String,
+    // Share of the draw relative to the other proxies; 1 when not given
+    pub weight: u32,
+}
+
+/// A proxy from `PROXY_POOL` together with a client already configured for it,
+/// so rotating between proxies doesn't cost a new connection pool per request.
+pub struct PoolEntry {
+    pub addr: String,
+    pub weight: u32,
+    pub client: Client,
+}
+
+/// Rotation over the proxies listed in `PROXY_POOL`: round-robin while they
+/// all weigh the same, otherwise a random draw proportional to their weights.
+pub struct ProxyPool {
+    entries: Vec<PoolEntry>,
+    next: AtomicUsize,
+    weighted: bool,
+}
+
+impl ProxyPool {
+    pub fn new(entries: Vec<PoolEntry>) -> Self {
+        let weighted = entries.iter().any(|entry| entry.weight != entries[0].weight);
+        ProxyPool {
+            entries,
+            next: AtomicUsize::new(0),
+            weighted,
+        }
+    }
+
+    /// Splits a comma-separated `PROXY_POOL` value into proxies, each with an
+    /// optional positive weight after a `|`. Fails with the offending entry's
+    /// address and weight.
+    pub fn parse(value: &str) -> Result<Vec<PoolProxy>, (String, String)> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| match entry.rsplit_once('|') {
+                Some((addr, weight)) => match weight.trim().parse() {
+                    Ok(weight) if weight > 0 => Ok(PoolProxy {
+                        addr: addr.trim().to_string(),
+                        weight,
+                    }),
+                    _ => Err((addr.trim().to_string(), weight.to_string())),
+                },
+                None => Ok(PoolProxy {
+                    addr: entry.to_string(),
+                    weight: 1,
+                }),
+            })
+            .collect()
+    }
+
+    /// Whether proxies are drawn by weight rather than in turn.
+    pub fn is_weighted(&self) -> bool {
+        self.weighted
+    }
+
+    /// Returns the next proxy that `usable` accepts, or `None` when the pool
+    /// is empty or every proxy was turned down. A proxy turned down is left
+    /// out of the rest of the draw, so the others keep their proportions.
+    pub fn next_where(&self, mut usable: impl FnMut(&str) -> bool) -> Option<&PoolEntry> {
+        if self.weighted {
+            return self.draw_where(usable);
+        }
+        for _ in 0..self.entries.len() {
+            let index = self.next.fetch_add(1, Ordering::Relaxed) % self.entries.len();
+            let entry = &self.entries[index];
+            if usable(&entry.addr) {
+                return Some(entry);
+            }
+        }
+        None
+    }
+
+    /// The proxy `next_where` would return now, without moving the rotation
+    /// on. A weighted pool draws at random either way, so this is one draw.
+    pub fn peek_where(&self, mut usable: impl FnMut(&str) -> bool) -> Option<&PoolEntry> {
+        if self.weighted {
+            return self.draw_where(usable);
+        }
+        let start = self.next.load(Ordering::Relaxed);
+        (0..self.entries.len())
+            .map(|offset| &self.entries[start.wrapping_add(offset) % self.entries.len()])
+            .find(|entry| usable(&entry.addr))
+    }
+
+    fn draw_where(&self, mut usable: impl FnMut(&str) -> bool) -> Option<&PoolEntry> {
+        let mut candidates: Vec<&PoolEntry> = self.entries.iter().collect();
+        let mut rng = rand::rng();
+        while !candidates.is_empty() {
+            let total: u64 = candidates.iter().map(|entry| u64::from(entry.weight)).sum();
+            let mut point = rng.random_range(0..total);
+            let index = candidates
+                .iter()
+                .position(|entry| match point.checked_sub(u64::from(entry.weight)) {
+                    Some(rest) => {
+                        point = rest;
+                        false
+                    }
+                    None => true,
+                })
+                .expect("the point is below the total weight");
+            let entry = candidates.swap_remove(index);
+            if usable(&entry.addr) {
+                return Some(entry);
+            }
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit_breaker::CircuitBreakers;
+    use std::time::Duration;
+
+    fn pool(proxies: &[(&str, u32)]) -> ProxyPool {
+        ProxyPool::new(
+            proxies
+                .iter()
+                .map(|&(addr, weight)| PoolEntry {
+                    addr: addr.to_string(),
+                    weight,
+                    client: Client::new(),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn rotation_cycles_through_every_proxy() {
+        let pool = pool(&[("socks5h://a:9050", 1), ("socks5h://b:9050", 1), ("socks5h://c:9050", 1)]);
+        assert!(!pool.is_weighted());
+        let picked: Vec<&str> = (0..6).map(|_| pool.next_where(|_| true).unwrap().addr.as_str()).collect();
+        assert_eq!(
+            picked,
+            ["socks5h://a:9050", "socks5h://b:9050", "socks5h://c:9050"].repeat(2)
+        );
+    }
+
+    #[test]
+    fn rotation_skips_unusable_proxies() {
+        let pool = pool(&[("a", 1), ("b", 1), ("c", 1)]);
+        let picked: Vec<&str> = (0..4).map(|_| pool.next_where(|addr| addr != "b").unwrap().addr.as_str()).collect();
+        assert_eq!(picked, ["a", "c", "a", "c"]);
+        assert!(pool.next_where(|_| false).is_none());
+        assert!(ProxyPool::new(Vec::new()).next_where(|_| true).is_none());
+    }
+
+    #[test]
+    fn parse_splits_a_comma_separated_list() {
+        let proxies = ProxyPool::parse(" socks5h://a:9050 , socks5h://b:9050,,").unwrap();
+        let addrs: Vec<&str> = proxies.iter().map(|proxy| proxy.addr.as_str()).collect();
+        assert_eq!(addrs, ["socks5h://a:9050", "socks5h://b:9050"]);
+    }
+
+    #[test]
+    fn parse_reads_optional_weights() {
+        let proxies = ProxyPool::parse("socks5://a:9050|3, socks5://b:9050").unwrap();
+        let parsed: Vec<(&str, u32)> = proxies.iter().map(|proxy| (proxy.addr.as_str(), proxy.weight)).collect();
+        assert_eq!(parsed, [("socks5://a:9050", 3), ("socks5://b:9050", 1)]);
+        assert_eq!(ProxyPool::parse("a|0").err(), Some(("a".to_string(), "0".to_string())));
+        assert_eq!(ProxyPool::parse("a|heavy").err(), Some(("a".to_string(), "heavy".to_string())));
+    }
+
+    #[test]
+    fn weighted_draw_follows_the_weights() {
+        let pool = pool(&[("a", 3), ("b", 1)]);
+        assert!(pool.is_weighted());
+        let draws = 10_000;
+        let a = (0..draws).filter(|_| pool.next_where(|_| true).unwrap().addr == "a").count();
+        // Expected 7500; this is over 10 standard deviations either way
+        assert!((7050..7950).contains(&a), "a drawn {} times out of {}", a, draws);
+    }
+
+    #[test]
+    fn open_circuits_leave_the_weighted_draw() {
+        let breakers = CircuitBreakers::new(1, Duration::from_secs(60));
+        breakers.record("a", false);
+        let pool = pool(&[("a", 100), ("b", 1), ("c", 1)]);
+        let picked: Vec<&str> = (0..200)
+            .map(|_| pool.next_where(|addr| breakers.allow(addr)).unwrap().addr.as_str())
+            .collect();
+        assert!(!picked.contains(&"a"));
+        assert!(picked.contains(&"b") && picked.contains(&"c"));
+    }
+
+    #[test]
+    fn peek_leaves_the_rotation_where_it_was() {
+        let pool = pool(&[("a", 1), ("b", 1)]);
+        assert_eq!(pool.next_where(|_| true).unwrap().addr, "a");
+        assert_eq!(pool.peek_where(|_| true).unwrap().addr, "b");
+        assert_eq!(pool.peek_where(|addr| addr != "b").unwrap().addr, "a");
+        assert_eq!(pool.next_where(|_| true).unwrap().addr, "b");
+    }
+}
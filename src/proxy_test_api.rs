@@ -0,0 +1,119 @@
+// proxy_test_api.rs
+//
+// POST /proxy/test: a proxy checked by fetching EXIT_IP_URL through it,
+// reporting the exit IP and how long the round trip took.
+use crate::{
+    error_body, query_exit_ip, request_id, select_client, with_request_id, ClientOptions, ProxyFields, ScrapeError,
+};
+use crate::circuit_breaker::CircuitBreakers;
+use crate::config::Config;
+use crate::proxy_pool::ProxyPool;
+use crate::redact;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::Instant;
+use tracing::{field, info, info_span, warn, Instrument};
+
+// How long a proxy test waits for EXIT_IP_URL when the request doesn't say;
+// a working proxy answers well within it, so a dead one fails fast
+const DEFAULT_PROXY_TEST_TIMEOUT_SECONDS: u64 = 10;
+
+// Define the structure for the incoming proxy test POST request
+#[derive(Deserialize)]
+pub struct ProxyTestRequest {
+    // Optional proxy to test, with its type, credentials and timeouts as in
+    // `ScrapeRequest`; without one, the proxy a scrape would use is tested.
+    // The timeout covers the whole probe.
+    #[serde(flatten)]
+    proxy_fields: ProxyFields,
+}
+
+// Outcome of a proxy test
+#[derive(Serialize)]
+struct ProxyTestResponse {
+    // The proxy tested, with credentials masked
+    proxy: String,
+    // Whether EXIT_IP_URL answered through the proxy
+    reachable: bool,
+    // How long it took to answer, connecting through the proxy included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u64>,
+    // The IP EXIT_IP_URL saw the probe come from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_ip: Option<IpAddr>,
+    // Why the proxy is unreachable, with its class as in `ScrapeResponse`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+/// Handles the POST request to test a proxy.
+///
+/// Asks EXIT_IP_URL for its caller's IP through the proxy in the request, or
+/// without one through the proxy a scrape would use, and reports whether it
+/// answered, how quickly and from which exit IP. A proxy that fails within
+/// the timeout, 10 seconds unless the request says otherwise, is reported as
+/// unreachable with a 200; only a request that can't be tested is an error.
+pub async fn proxy_test_handler(
+    http_req: HttpRequest,
+    req: web::Json<ProxyTestRequest>,
+    config: web::Data<Config>,
+    base_client: web::Data<Client>,
+    proxy_pool: web::Data<ProxyPool>,
+    breakers: web::Data<CircuitBreakers>,
+) -> impl Responder {
+    let request_id = request_id(&http_req);
+    let span = info_span!("proxy_test", request_id = %request_id, proxy = field::Empty);
+
+    let selected = span.in_scope(|| {
+        let fields = &req.proxy_fields;
+        let client_options = ClientOptions {
+            timeout_seconds: Some(fields.timeout_seconds.unwrap_or(DEFAULT_PROXY_TEST_TIMEOUT_SECONDS)),
+            // The proxy asked for is the one tested, whatever the default
+            override_default_proxy: fields.proxy.is_some(),
+            ..ClientOptions::from_proxy_fields(fields)?
+        };
+        let client = select_client(&config, &base_client, &proxy_pool, &breakers, &client_options)?;
+        match &client.proxy {
+            Some(proxy) => Ok((redact::proxy_url(proxy), client)),
+            None => Err(ScrapeError::NoProxyToTest),
+        }
+    });
+    let (proxy, client) = match selected {
+        Ok(selected) => selected,
+        Err(e) => return with_request_id(HttpResponse::build(e.status_code()).json(error_body(&e)), &request_id),
+    };
+
+    let started = Instant::now();
+    let answer = query_exit_ip(&config, &client.http).instrument(span.clone()).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let response = match answer {
+        Ok(exit_ip) => {
+            span.in_scope(|| info!(latency_ms, exit_ip = ?exit_ip, "Proxy test succeeded"));
+            ProxyTestResponse {
+                proxy,
+                reachable: true,
+                latency_ms: Some(latency_ms),
+                exit_ip,
+                error: None,
+                error_code: None,
+            }
+        }
+        Err(e) => {
+            let e = ScrapeError::Request(e);
+            span.in_scope(|| warn!(error = %e, "Proxy test failed"));
+            ProxyTestResponse {
+                proxy,
+                reachable: false,
+                latency_ms: None,
+                exit_ip: None,
+                error: Some(e.to_string()),
+                error_code: Some(e.code()),
+            }
+        }
+    };
+    with_request_id(HttpResponse::Ok().json(response), &request_id)
+}
@@ -0,0 +1,128 @@
+// rate_limit.rs
+//
+// Per-client rate limiting. When API_KEYS is set, scrape requests must carry
+// one of the listed keys in X-Api-Key, and each key gets a token bucket
+// refilling at RATE_LIMIT_PER_MINUTE that also allows a burst of that size.
+// Unknown or missing keys get a 401, exhausted buckets a 429 with Retry-After.
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// Header identifying the calling client
+const API_KEY_HEADER: &str = "x-api-key";
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token buckets for the keys in `API_KEYS`, shared through `web::Data`.
+pub struct RateLimiter {
+    // Tokens a bucket holds when full, and how many it regains per minute
+    per_minute: u32,
+    // One bucket per allowed key
+    buckets: Mutex<HashMap<String, Bucket>>,
+    // Whether any keys are configured; without them requests aren't checked
+    enabled: bool,
+}
+
+impl RateLimiter {
+    pub fn new(api_keys: &[String], per_minute: u32) -> Self {
+        let now = Instant::now();
+        let buckets = api_keys
+            .iter()
+            .map(|key| {
+                let bucket = Bucket {
+                    tokens: f64::from(per_minute),
+                    refilled_at: now,
+                };
+                (key.clone(), bucket)
+            })
+            .collect();
+        RateLimiter {
+            per_minute,
+            buckets: Mutex::new(buckets),
+            enabled: !api_keys.is_empty(),
+        }
+    }
+
+    /// Takes a token from the bucket of `key`. Fails with `None` for an
+    /// unknown key, or with how long until a token is available.
+    fn acquire(&self, key: &str) -> Result<(), Option<Duration>> {
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        let bucket = buckets.get_mut(key).ok_or(None)?;
+
+        let per_second = f64::from(self.per_minute) / 60.0;
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * per_second;
+        bucket.tokens = (bucket.tokens + refill).min(f64::from(self.per_minute));
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Some(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second)))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RejectedResponse {
+    error: &'static str,
+}
+
+/// Middleware authenticating the caller by API key and applying its rate
+/// limit. Does nothing when no keys are configured.
+pub async fn limit_by_api_key(
+    req: ServiceRequest,
+    next: Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let Some(limiter) = req.app_data::<web::Data<RateLimiter>>().cloned() else {
+        return next.call(req).await;
+    };
+    if !limiter.enabled {
+        return next.call(req).await;
+    }
+
+    let key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let Some(key) = key else {
+        warn!(path = req.path(), "Rejecting request without an API key");
+        let response = HttpResponse::Unauthorized().json(RejectedResponse {
+            error: "Missing X-Api-Key header",
+        });
+        return Ok(req.into_response(response));
+    };
+
+    match limiter.acquire(key) {
+        Ok(()) => next.call(req).await,
+        Err(None) => {
+            warn!(path = req.path(), "Rejecting request with an unknown API key");
+            let response = HttpResponse::Unauthorized().json(RejectedResponse {
+                error: "Unknown API key",
+            });
+            Ok(req.into_response(response))
+        }
+        Err(Some(wait)) => {
+            // Whole seconds, rounded up so a retry at that point succeeds
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            warn!(path = req.path(), retry_after, "Rate limit exceeded");
+            let response = HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, retry_after.to_string()))
+                .json(RejectedResponse {
+                    error: "Rate limit exceeded",
+                });
+            Ok(req.into_response(response))
+        }
+    }
+}
@@ -0,0 +1,248 @@
+// readability.rs
+//
+// Main-content extraction for the "readability" output mode, in the manner of
+// Arc90's Readability: paragraphs are scored by their length and commas, the
+// scores flow to their parent and grandparent elements, and the element with
+// the best score, discounted by how much of its text is links, is taken as the
+// article, together with any siblings that score nearly as well.
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashMap;
+
+// Shortest paragraph, in characters, that counts towards its ancestors' scores
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+// Elements whose text is scored as a paragraph
+const PARAGRAPH_SELECTOR: &str = "p, pre, td, blockquote";
+// Elements never part of an article, skipped with their contents
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "template", "head", "nav", "aside", "footer", "form", "button",
+    "iframe", "svg", "canvas", "select", "dialog",
+];
+// Elements that start a new line of text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "blockquote", "br", "dd", "div", "dl", "dt", "figcaption", "figure",
+    "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "main", "ol", "p", "pre", "section",
+    "table", "td", "th", "tr", "ul",
+];
+// Class and id words (or their beginnings, past three letters) that mark an
+// element as likely content
+const POSITIVE_HINTS: &[&str] = &[
+    "article", "body", "content", "entry", "main", "page", "post", "text", "blog", "story",
+];
+// Class and id words (or their beginnings, past three letters) that mark an
+// element as likely boilerplate
+const NEGATIVE_HINTS: &[&str] = &[
+    "comment", "footer", "nav", "navbar", "navigation", "sidebar", "sponsor", "ad", "ads", "advert",
+    "banner", "menu", "share", "social", "related", "promo", "masthead", "header", "cookie", "popup",
+    "widget", "breadcrumb", "pagination",
+];
+
+/// The main content of a page.
+pub struct Article {
+    // The article's own title, without the site name where it can be told apart
+    pub title: Option<String>,
+    // Readable text of the article, one line per block
+    pub text: String,
+}
+
+/// Finds the article in an HTML page. A page with no paragraph long enough to
+/// score gives the text of its whole body, still without boilerplate.
+pub fn extract(html: &str) -> Article {
+    let document = Html::parse_document(html);
+    let title = title(&document);
+
+    let paragraphs = Selector::parse(PARAGRAPH_SELECTOR).expect("static selector is valid");
+    let mut scores: HashMap<_, f64> = HashMap::new();
+    let mut candidates = Vec::new();
+    for paragraph in document.select(&paragraphs) {
+        if is_unlikely(paragraph) {
+            continue;
+        }
+        let text = one_line(&paragraph.text().collect::<String>());
+        let length = text.chars().count();
+        if length < MIN_PARAGRAPH_LENGTH {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (length as f64 / 100.0).min(3.0);
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (level, ancestor) in ancestors.enumerate() {
+            let entry = scores.entry(ancestor.id()).or_insert_with(|| {
+                candidates.push(ancestor);
+                base_score(ancestor)
+            });
+            // A grandparent gets half as much as a parent
+            *entry += score / (level + 1) as f64;
+        }
+    }
+
+    // Link-heavy blocks are menus and lists of related articles, not prose
+    let final_score = |element: ElementRef<'_>| scores[&element.id()] * (1.0 - link_density(element));
+    let best = candidates
+        .iter()
+        .copied()
+        .map(|candidate| (candidate, final_score(candidate)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    let mut text = String::new();
+    match best {
+        Some((top, top_score)) => {
+            // Articles are often split into sibling blocks by ads or images
+            let threshold = (top_score * 0.2).max(10.0);
+            let siblings: Vec<ElementRef<'_>> = match top.parent().and_then(ElementRef::wrap) {
+                Some(parent) => parent.children().filter_map(ElementRef::wrap).collect(),
+                None => vec![top],
+            };
+            for sibling in siblings {
+                let related = sibling.id() == top.id()
+                    || (scores.contains_key(&sibling.id()) && final_score(sibling) >= threshold)
+                    || (sibling.value().name() == "p" && is_prose(sibling));
+                if related && !is_unlikely(sibling) {
+                    text.push('\n');
+                    collect_text(sibling, &mut text);
+                    text.push('\n');
+                }
+            }
+        }
+        None => {
+            let body = Selector::parse("body").expect("static selector is valid");
+            collect_text(document.select(&body).next().unwrap_or_else(|| document.root_element()), &mut text);
+        }
+    }
+    Article {
+        title,
+        text: collapse_whitespace(&text),
+    }
+}
+
+/// The page's title: its first <h1> when the <title> contains it, as the
+/// <title> usually adds the site's name, otherwise the OpenGraph title, the
+/// <title> or the <h1>, whichever comes first.
+fn title(document: &Html) -> Option<String> {
+    let first_text = |selector: &str| {
+        let selector = Selector::parse(selector).expect("static selector is valid");
+        document
+            .select(&selector)
+            .next()
+            .map(|element| one_line(&element.text().collect::<String>()))
+            .filter(|text| !text.is_empty())
+    };
+    let og_title = Selector::parse(r#"meta[property="og:title"]"#).expect("static selector is valid");
+    let og_title = document
+        .select(&og_title)
+        .next()
+        .and_then(|meta| meta.value().attr("content"))
+        .map(one_line)
+        .filter(|text| !text.is_empty());
+    let page_title = first_text("title");
+    let heading = first_text("h1");
+    match (&page_title, heading) {
+        (Some(page_title), Some(heading)) if page_title.contains(&heading) => Some(heading),
+        (_, heading) => og_title.or(page_title).or(heading),
+    }
+}
+
+/// Starting score of a candidate, from its tag and its class and id.
+fn base_score(element: ElementRef<'_>) -> f64 {
+    let tag = match element.value().name() {
+        "article" => 10.0,
+        "div" | "main" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "form" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    tag + hint_weight(element)
+}
+
+/// +25 for a class or id suggesting content, -25 for one suggesting boilerplate.
+fn hint_weight(element: ElementRef<'_>) -> f64 {
+    let names = format!(
+        "{} {}",
+        element.value().attr("class").unwrap_or_default(),
+        element.value().attr("id").unwrap_or_default()
+    )
+    .to_ascii_lowercase();
+    let words: Vec<&str> = names
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    // Short hints like "ad" must be whole words, or "address" would count
+    let matches = |hints: &[&str]| {
+        hints.iter().any(|hint| {
+            words.iter().any(|word| *word == *hint || (hint.len() > 3 && word.starts_with(hint)))
+        })
+    };
+    let mut weight = 0.0;
+    if matches(POSITIVE_HINTS) {
+        weight += 25.0;
+    }
+    if matches(NEGATIVE_HINTS) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+/// Whether an element or one of its ancestors is boilerplate: a skipped
+/// element, or one whose class or id only suggests boilerplate.
+fn is_unlikely(element: ElementRef<'_>) -> bool {
+    std::iter::once(element)
+        .chain(element.ancestors().filter_map(ElementRef::wrap))
+        .any(|element| SKIPPED_ELEMENTS.contains(&element.value().name()) || hint_weight(element) < 0.0)
+}
+
+/// Share of an element's text that sits inside links.
+fn link_density(element: ElementRef<'_>) -> f64 {
+    let length = one_line(&element.text().collect::<String>()).len();
+    if length == 0 {
+        return 0.0;
+    }
+    let links = Selector::parse("a").expect("static selector is valid");
+    let link_length: usize = element
+        .select(&links)
+        .map(|link| one_line(&link.text().collect::<String>()).len())
+        .sum();
+    (link_length as f64 / length as f64).min(1.0)
+}
+
+/// Whether a paragraph outside the best candidate still reads as part of the
+/// article: long with few links, or a short sentence without any.
+fn is_prose(paragraph: ElementRef<'_>) -> bool {
+    let text = one_line(&paragraph.text().collect::<String>());
+    let density = link_density(paragraph);
+    (text.len() > 80 && density < 0.25) || (!text.is_empty() && density == 0.0 && text.contains(". "))
+}
+
+/// Appends the text of an element, leaving out boilerplate inside it.
+fn collect_text(element: ElementRef<'_>, out: &mut String) {
+    for child in element.children() {
+        if let Some(text) = child.value().as_text() {
+            out.push_str(text);
+        } else if let Some(child) = ElementRef::wrap(child) {
+            let name = child.value().name();
+            if SKIPPED_ELEMENTS.contains(&name) || hint_weight(child) < 0.0 {
+                continue;
+            }
+            let is_block = BLOCK_ELEMENTS.contains(&name);
+            if is_block {
+                out.push('\n');
+            }
+            collect_text(child, out);
+            if is_block {
+                out.push('\n');
+            }
+        }
+    }
+}
+
+/// Joins text onto a single line.
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Collapses runs of whitespace within each line and drops empty lines.
+fn collapse_whitespace(text: &str) -> String {
+    text.lines()
+        .map(one_line)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,117 @@
+// response_cache.rs
+//
+// Short-lived in-memory cache of fetched pages. Callers opt in per request
+// with a TTL; the cache is bounded in entries and evicts expired entries
+// first, then the least recently used.
+use reqwest::header::HeaderMap;
+use reqwest::Method;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+struct Entry<V> {
+    stored_at: Instant,
+    expires_at: Instant,
+    // Logical clock value of the latest hit, for LRU eviction
+    last_used: u64,
+    value: Arc<V>,
+}
+
+struct Entries<V> {
+    map: HashMap<String, Entry<V>>,
+    clock: u64,
+}
+
+/// Process-wide cache of fetched responses, shared through `web::Data`.
+pub struct ResponseCache<V> {
+    max_entries: usize,
+    entries: Mutex<Entries<V>>,
+}
+
+impl<V> ResponseCache<V> {
+    /// Creates a cache holding at most `max_entries` responses; 0 disables it.
+    pub fn new(max_entries: usize) -> Self {
+        ResponseCache {
+            max_entries,
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// Returns the response stored under `key` if it's younger than `max_age`.
+    pub fn get(&self, key: &str, max_age: Duration) -> Option<Arc<V>> {
+        let mut entries = self.entries.lock().expect("response cache lock poisoned");
+        entries.clock += 1;
+        let clock = entries.clock;
+        let now = Instant::now();
+        let entry = entries.map.get_mut(key)?;
+        if now >= entry.expires_at || now.duration_since(entry.stored_at) >= max_age {
+            return None;
+        }
+        entry.last_used = clock;
+        Some(entry.value.clone())
+    }
+
+    /// Stores a response under `key` for `ttl`, making room if the cache is full.
+    pub fn insert(&self, key: String, value: V, ttl: Duration) {
+        if self.max_entries == 0 || ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().expect("response cache lock poisoned");
+        entries.clock += 1;
+        let clock = entries.clock;
+        let now = Instant::now();
+
+        if !entries.map.contains_key(&key) && entries.map.len() >= self.max_entries {
+            entries.map.retain(|_, entry| entry.expires_at > now);
+            if entries.map.len() >= self.max_entries {
+                let oldest = entries
+                    .map
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.map.remove(&oldest);
+                }
+            }
+        }
+
+        entries.map.insert(
+            key,
+            Entry {
+                stored_at: now,
+                expires_at: now + ttl,
+                last_used: clock,
+                value: Arc::new(value),
+            },
+        );
+    }
+}
+
+/// Builds the cache key for a request, or `None` when its response
+/// shouldn't be cached: only GET and HEAD are. The key covers the method,
+/// the URL without its fragment and every request header, since any of them
+/// (Accept-Language, Cookie, User-Agent, ...) may change the response.
+pub fn cache_key(method: &Method, url: &str, headers: &HeaderMap) -> Option<String> {
+    if method != Method::GET && method != Method::HEAD {
+        return None;
+    }
+    let mut url = Url::parse(url).ok()?;
+    url.set_fragment(None);
+
+    let mut header_lines: Vec<String> = headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes())))
+        .collect();
+    header_lines.sort();
+
+    let mut key = format!("{} {}", method, url);
+    for line in header_lines {
+        key.push('\n');
+        key.push_str(&line);
+    }
+    Some(key)
+}
@@ -0,0 +1,333 @@
+// robots.rs
+//
+// robots.txt support following RFC 9309: rules are grouped by user-agent, the
+// longest matching rule decides (Allow wins a tie), and `*` and `$` work as
+// wildcards. Crawl-delay and Sitemap lines, which the RFC leaves out, are kept
+// as most sites write them. Parsed files are cached per origin for a
+// configurable TTL.
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// An Allow or Disallow line.
+#[derive(Serialize, Clone)]
+pub struct Rule {
+    pub allow: bool,
+    #[serde(rename = "path")]
+    pub pattern: String,
+}
+
+// Rules that apply to the user-agents listed above them
+struct Group {
+    // Lowercased product tokens, or "*"
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+    // Seconds to wait between requests, from a Crawl-delay line
+    crawl_delay: Option<f64>,
+}
+
+/// The part of a robots.txt that applies to one crawler.
+#[derive(Serialize)]
+pub struct AgentRules {
+    // The user-agent the groups applied were written for: the crawler's
+    // product token or "*"; absent when no group applies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_agent: Option<String>,
+    pub rules: Vec<Rule>,
+    #[serde(rename = "crawl_delay_seconds", skip_serializing_if = "Option::is_none")]
+    pub crawl_delay: Option<f64>,
+}
+
+/// A parsed robots.txt.
+pub struct Robots {
+    groups: Vec<Group>,
+    // Sitemap URLs, which apply whatever the group
+    sitemaps: Vec<String>,
+}
+
+impl Robots {
+    /// Parses a robots.txt body. Unknown lines and rules outside a group are ignored.
+    pub fn parse(text: &str) -> Self {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut sitemaps = Vec::new();
+        // Whether the current group already has rules, so a further
+        // user-agent line starts a new group instead of joining it
+        let mut in_rules = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules || groups.is_empty() {
+                        groups.push(Group {
+                            agents: Vec::new(),
+                            rules: Vec::new(),
+                            crawl_delay: None,
+                        });
+                        in_rules = false;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.agents.push(value.to_ascii_lowercase());
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_rules = true;
+                    // An empty Disallow allows everything, which is the default anyway
+                    if value.is_empty() {
+                        continue;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_rules = true;
+                    let delay = value.parse::<f64>().ok().filter(|delay| delay.is_finite() && *delay >= 0.0);
+                    if let (Some(group), Some(delay)) = (groups.last_mut(), delay) {
+                        group.crawl_delay = Some(delay);
+                    }
+                }
+                // Not tied to a group, so it doesn't end one either
+                "sitemap" if !value.is_empty() => sitemaps.push(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Robots { groups, sitemaps }
+    }
+
+    /// A robots.txt that allows everything, used when the site has none.
+    pub fn allow_all() -> Self {
+        Robots {
+            groups: Vec::new(),
+            sitemaps: Vec::new(),
+        }
+    }
+
+    /// A robots.txt that disallows everything, used when it couldn't be fetched.
+    pub fn disallow_all() -> Self {
+        Robots {
+            groups: vec![Group {
+                agents: vec!["*".to_string()],
+                rules: vec![Rule {
+                    allow: false,
+                    pattern: "/".to_string(),
+                }],
+                crawl_delay: None,
+            }],
+            sitemaps: Vec::new(),
+        }
+    }
+
+    /// Whether `path` (including any query string) may be fetched by the
+    /// crawler identified by `user_agent`.
+    pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        self.groups_for(user_agent)
+            .1
+            .iter()
+            .flat_map(|group| &group.rules)
+            .filter(|rule| matches_pattern(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    /// The rules and crawl delay for the crawler identified by `user_agent`,
+    /// the first delay given winning when its groups give several.
+    pub fn rules_for(&self, user_agent: &str) -> AgentRules {
+        let (matched_agent, groups) = self.groups_for(user_agent);
+        AgentRules {
+            matched_agent: (!groups.is_empty()).then_some(matched_agent),
+            rules: groups.iter().flat_map(|group| group.rules.iter().cloned()).collect(),
+            crawl_delay: groups.iter().find_map(|group| group.crawl_delay),
+        }
+    }
+
+    /// The Sitemap URLs listed, in order.
+    pub fn sitemaps(&self) -> &[String] {
+        &self.sitemaps
+    }
+
+    // The groups that apply to `user_agent`, with the agent they were written for
+    fn groups_for(&self, user_agent: &str) -> (String, Vec<&Group>) {
+        let token = product_token(user_agent);
+        let named: Vec<&Group> = self
+            .groups
+            .iter()
+            .filter(|group| group.agents.contains(&token))
+            .collect();
+        // Groups for our user-agent replace the catch-all group entirely
+        if named.is_empty() {
+            let catch_all = self
+                .groups
+                .iter()
+                .filter(|group| group.agents.iter().any(|agent| agent == "*"))
+                .collect();
+            ("*".to_string(), catch_all)
+        } else {
+            (token, named)
+        }
+    }
+}
+
+/// The product token robots.txt groups are matched against: the start of the
+/// User-Agent up to its version or comment, lowercased, e.g. "mybot" for
+/// "MyBot/1.0 (+https://example.com)".
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Matches a rule against a path: rules are prefixes, `*` matches any run of
+/// characters and a trailing `$` anchors the rule at the end of the path.
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// Parsed robots.txt files by origin, each kept for the configured TTL.
+pub struct RobotsCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, Arc<Robots>)>>,
+}
+
+impl RobotsCache {
+    pub fn new(ttl: Duration) -> Self {
+        RobotsCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached robots.txt for `origin` unless it has expired.
+    pub fn get(&self, origin: &str) -> Option<Arc<Robots>> {
+        let entries = self.entries.lock().expect("robots cache lock poisoned");
+        entries
+            .get(origin)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+            .map(|(_, robots)| robots.clone())
+    }
+
+    /// Stores the robots.txt for `origin`, dropping expired entries on the way.
+    pub fn insert(&self, origin: String, robots: Arc<Robots>) {
+        let mut entries = self.entries.lock().expect("robots cache lock poisoned");
+        entries.retain(|_, (fetched_at, _)| fetched_at.elapsed() < self.ttl);
+        entries.insert(origin, (Instant::now(), robots));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_are_prefixes_with_wildcards_and_anchors() {
+        assert!(matches_pattern("/private", "/private/page"));
+        assert!(!matches_pattern("/private", "/public"));
+        assert!(matches_pattern("/*.pdf", "/docs/file.pdf?download=1"));
+        assert!(matches_pattern("/*.pdf$", "/docs/file.pdf"));
+        assert!(!matches_pattern("/*.pdf$", "/docs/file.pdf?download=1"));
+        assert!(matches_pattern("/a*b*c", "/a-b-c-d"));
+        assert!(!matches_pattern("/a*c*b", "/a-b-c"));
+        assert!(matches_pattern("/exact$", "/exact"));
+        assert!(!matches_pattern("/exact$", "/exactly"));
+    }
+
+    #[test]
+    fn longest_rule_wins_and_allow_wins_a_tie() {
+        let robots = Robots::parse(
+            "User-agent: *\n\
+             Disallow: /shop\n\
+             Allow: /shop/public\n\
+             Disallow: /same\n\
+             Allow: /same\n",
+        );
+        assert!(!robots.is_allowed("AnyBot", "/shop/cart"));
+        assert!(robots.is_allowed("AnyBot", "/shop/public/item"));
+        assert!(robots.is_allowed("AnyBot", "/same"));
+        assert!(robots.is_allowed("AnyBot", "/elsewhere"));
+        assert!(robots.is_allowed("AnyBot", "/robots.txt"));
+    }
+
+    #[test]
+    fn named_groups_replace_the_catch_all() {
+        let robots = Robots::parse(
+            "# comment\n\
+             User-agent: *\n\
+             Disallow: /\n\
+             \n\
+             User-agent: OtherBot\n\
+             User-agent: MyBot\n\
+             Disallow: /admin # trailing comment\n",
+        );
+        assert!(robots.is_allowed("MyBot/1.0 (+https://example.com)", "/page"));
+        assert!(!robots.is_allowed("mybot", "/admin/users"));
+        assert!(!robots.is_allowed("SomeoneElse", "/page"));
+    }
+
+    #[test]
+    fn rules_crawl_delay_and_sitemaps_follow_the_matched_group() {
+        let robots = Robots::parse(
+            "Sitemap: https://example.com/sitemap.xml\n\
+             User-agent: *\n\
+             Crawl-delay: 10\n\
+             Disallow: /\n\
+             \n\
+             User-agent: MyBot\n\
+             Crawl-delay: 2.5\n\
+             Allow: /public\n\
+             Disallow: /admin\n\
+             Sitemap: https://example.com/news.xml\n",
+        );
+        let mine = robots.rules_for("MyBot/1.0");
+        assert_eq!(mine.matched_agent.as_deref(), Some("mybot"));
+        let rules: Vec<(bool, &str)> = mine.rules.iter().map(|rule| (rule.allow, rule.pattern.as_str())).collect();
+        assert_eq!(rules, [(true, "/public"), (false, "/admin")]);
+        assert_eq!(mine.crawl_delay, Some(2.5));
+
+        let others = robots.rules_for("SomeoneElse");
+        assert_eq!(others.matched_agent.as_deref(), Some("*"));
+        assert_eq!(others.crawl_delay, Some(10.0));
+        assert_eq!(robots.sitemaps(), ["https://example.com/sitemap.xml", "https://example.com/news.xml"]);
+
+        let none = Robots::allow_all().rules_for("MyBot");
+        assert!(none.matched_agent.is_none() && none.rules.is_empty() && none.crawl_delay.is_none());
+    }
+
+    #[test]
+    fn missing_and_unreachable_files() {
+        assert!(Robots::allow_all().is_allowed("*", "/anything"));
+        assert!(Robots::parse("").is_allowed("*", "/anything"));
+        assert!(!Robots::disallow_all().is_allowed("*", "/anything"));
+    }
+}
@@ -0,0 +1,143 @@
+// robots_api.rs
+//
+// POST /robots: the robots.txt rules, crawl delay and sitemaps a site gives our
+// User-Agent, read through the same cache as `respect_robots`.
+use crate::{
+    error_body, normalize_url, request_id, robots_for_origin, robots_path, robots_user_agent, select_client,
+    with_hard_timeout, with_request_id, ClientOptions, FetchOptions, ProxyFields, ScrapeError,
+};
+use crate::circuit_breaker::CircuitBreakers;
+use crate::config::Config;
+use crate::politeness::HostThrottle;
+use crate::proxy_pool::ProxyPool;
+use crate::robots::{RobotsCache, Rule};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use reqwest::Client;
+use reqwest::header::{HeaderValue, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use tracing::{field, info_span, Instrument};
+
+// Define the structure for the incoming robots.txt lookup
+#[derive(Deserialize)]
+pub struct RobotsRequest {
+    // Any URL on the site; the robots.txt of its origin is looked up
+    url: String,
+    // Optional User-Agent whose rules are returned, instead of DEFAULT_USER_AGENT
+    user_agent: Option<String>,
+    // Optional proxy and timeouts for fetching robots.txt
+    #[serde(flatten)]
+    proxy_fields: ProxyFields,
+}
+
+// The robots.txt rules that apply to our User-Agent on a site
+#[derive(Serialize)]
+struct RobotsResponse {
+    robots_url: String,
+    // The User-Agent the rules were picked for, "*" when none is configured
+    user_agent: String,
+    // Whether the requested URL itself may be fetched
+    allowed: bool,
+    // The user-agent the applied groups were written for, if any applies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matched_agent: Option<String>,
+    rules: Vec<Rule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    crawl_delay_seconds: Option<f64>,
+    sitemaps: Vec<String>,
+    // Whether the robots.txt came from the cache rather than the site
+    cached: bool,
+}
+
+/// Looks up the robots.txt of a URL's site, through the same cache as
+/// `respect_robots`, and returns the rules, crawl delay and sitemaps that
+/// apply to our User-Agent, along with whether the URL itself is allowed. A
+/// site without robots.txt allows everything, and one whose robots.txt can't
+/// be fetched disallows everything, as for scrapes.
+pub async fn robots_handler(
+    http_req: HttpRequest,
+    req: web::Json<RobotsRequest>,
+    config: web::Data<Config>,
+    base_client: web::Data<Client>,
+    proxy_pool: web::Data<ProxyPool>,
+    robots_cache: web::Data<RobotsCache>,
+    throttle: web::Data<HostThrottle>,
+    breakers: web::Data<CircuitBreakers>,
+) -> impl Responder {
+    let request_id = request_id(&http_req);
+    let span = info_span!("robots", request_id = %request_id, url = %req.url, proxy = field::Empty);
+
+    let operation = async {
+        let url = normalize_url(&config, &req.url)?;
+        let parsed = url::Url::parse(&url).expect("normalized URLs parse");
+        let mut options = FetchOptions::new(&config);
+        if let Some(user_agent) = &req.user_agent {
+            let value = HeaderValue::from_str(user_agent)
+                .map_err(|_| ScrapeError::InvalidHeader(USER_AGENT.to_string()))?;
+            options.headers.insert(USER_AGENT, value);
+        }
+        let client_options = ClientOptions::from_proxy_fields(&req.proxy_fields)?;
+        let client = select_client(&config, &base_client, &proxy_pool, &breakers, &client_options)?;
+        let (robots, cached) =
+            robots_for_origin(&config, &throttle, &robots_cache, &client, &parsed, &options).await?;
+        let user_agent = robots_user_agent(&config, &options);
+        let rules = robots.rules_for(user_agent);
+        Ok(RobotsResponse {
+            robots_url: format!("{}/robots.txt", parsed.origin().ascii_serialization()),
+            user_agent: user_agent.to_string(),
+            allowed: robots.is_allowed(user_agent, &robots_path(&parsed)),
+            matched_agent: rules.matched_agent,
+            rules: rules.rules,
+            crawl_delay_seconds: rules.crawl_delay,
+            sitemaps: robots.sitemaps().to_vec(),
+            cached,
+        })
+    };
+    let response = match with_hard_timeout(&config, operation).instrument(span).await {
+        Ok(body) => HttpResponse::Ok().json(body),
+        Err(e) => HttpResponse::build(e.status_code()).json(error_body(&e)),
+    };
+    with_request_id(response, &request_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{json_response, response, serve, TestApp};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use std::sync::atomic::Ordering;
+
+    #[actix_web::test]
+    async fn robots_lookup_reports_the_rules_for_our_user_agent() {
+        let robots = "User-agent: *\nCrawl-delay: 3\nDisallow: /private\nSitemap: https://example.com/sitemap.xml\n";
+        let fixture = serve(move |_| response("200 OK", &[("content-type", "text/plain")], robots)).await;
+        let app = TestApp::new();
+        let lookup = |path: &str| {
+            let req = serde_json::from_value(serde_json::json!({ "url": format!("{}{}", fixture.url, path) }));
+            robots_handler(
+                TestRequest::default().to_http_request(),
+                web::Json(req.expect("request body deserializes")),
+                app.config.clone(),
+                app.client.clone(),
+                app.proxy_pool.clone(),
+                app.robots_cache.clone(),
+                app.throttle.clone(),
+                app.breakers.clone(),
+            )
+        };
+
+        let (status, body) = json_response(lookup("/private/page").await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["robots_url"], format!("{}/robots.txt", fixture.url));
+        assert_eq!(body["allowed"], false);
+        assert_eq!(body["matched_agent"], "*");
+        assert_eq!(body["rules"], serde_json::json!([{ "allow": false, "path": "/private" }]));
+        assert_eq!(body["crawl_delay_seconds"], 3.0);
+        assert_eq!(body["sitemaps"], serde_json::json!(["https://example.com/sitemap.xml"]));
+        assert_eq!(body["cached"], false);
+
+        let (_, body) = json_response(lookup("/public").await).await;
+        assert_eq!((body["allowed"].clone(), body["cached"].clone()), (true.into(), true.into()));
+        assert_eq!(fixture.connections.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,39 @@
+// sessions.rs
+//
+// Named cookie sessions. A request naming a `session_id` is sent with that
+// session's cookie jar, so cookies set by one scrape are sent back on the
+// next, as a browser would. Sessions idle for longer than SESSION_TTL_SECONDS
+// are forgotten.
+use reqwest::cookie::Jar;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Cookie jars by session id, shared through `web::Data`.
+pub struct CookieSessions {
+    ttl: Duration,
+    // Each jar with when its session was last used
+    jars: Mutex<HashMap<String, (Instant, Arc<Jar>)>>,
+}
+
+impl CookieSessions {
+    pub fn new(ttl: Duration) -> Self {
+        CookieSessions {
+            ttl,
+            jars: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the jar of `session_id`, starting an empty one for a new or
+    /// expired session, and drops sessions that have been idle too long.
+    pub fn jar(&self, session_id: &str) -> Arc<Jar> {
+        let mut jars = self.jars.lock().expect("cookie sessions lock poisoned");
+        let now = Instant::now();
+        jars.retain(|_, (used_at, _)| now.duration_since(*used_at) < self.ttl);
+        let (used_at, jar) = jars
+            .entry(session_id.to_string())
+            .or_insert_with(|| (now, Arc::new(Jar::default())));
+        *used_at = now;
+        jar.clone()
+    }
+}
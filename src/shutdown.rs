@@ -0,0 +1,122 @@
+// shutdown.rs
+//
+// Graceful shutdown: on SIGTERM or SIGINT the server keeps listening but
+// answers new requests with a 503, so callers and load balancers move on to
+// another instance, while in-flight requests get up to
+// SHUTDOWN_TIMEOUT_SECONDS to complete. The server stops once they're done or
+// the grace period runs out, whichever comes first.
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Shutdown state shared through `web::Data`: whether the server is draining
+/// and how many requests are still being handled.
+#[derive(Default)]
+pub struct Shutdown {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+impl Shutdown {
+    /// Marks the server as draining; from now on new requests are refused.
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Waits until no request is in flight, giving up after `timeout`.
+    /// Returns whether every request completed in time.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            loop {
+                // Register before checking so a wakeup in between isn't lost
+                let idle = self.idle.notified();
+                if self.in_flight.load(Ordering::SeqCst) == 0 {
+                    return;
+                }
+                idle.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
+
+// Counts a request as in flight for as long as it's alive
+struct InFlight<'a>(&'a Shutdown);
+
+impl<'a> InFlight<'a> {
+    fn start(shutdown: &'a Shutdown) -> Self {
+        shutdown.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(shutdown)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DrainingResponse {
+    error: &'static str,
+}
+
+/// Middleware answering 503 once shutdown has started, closing the connection
+/// so the caller's next attempt lands on another instance, and otherwise
+/// tracking the request as in flight.
+pub async fn reject_while_draining(
+    req: ServiceRequest,
+    next: Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let Some(shutdown) = req.app_data::<web::Data<Shutdown>>().cloned() else {
+        return next.call(req).await;
+    };
+    if shutdown.is_draining() {
+        let response = HttpResponse::ServiceUnavailable()
+            .force_close()
+            .json(DrainingResponse {
+                error: "Server is shutting down",
+            });
+        return Ok(req.into_response(response));
+    }
+    let _in_flight = InFlight::start(&shutdown);
+    next.call(req).await
+}
+
+/// Waits for SIGTERM (sent by Kubernetes when stopping a pod) or SIGINT and
+/// returns the name of the signal received.
+pub async fn wait_for_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = sigterm.recv() => "SIGTERM",
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+            },
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to install SIGTERM handler, only SIGINT will shut down");
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "SIGINT"
+    }
+}
@@ -0,0 +1,78 @@
+// single_flight.rs
+//
+// Coalescing of identical in-flight fetches: the first request for a key does
+// the fetch, and identical requests arriving meanwhile wait for its result
+// instead of fetching the same page again.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+// Where followers wait for the leader's result
+type Landing<V> = watch::Receiver<Option<Arc<V>>>;
+
+/// Fetches in flight by key, shared through `web::Data`.
+pub struct SingleFlight<V> {
+    in_flight: Mutex<HashMap<String, Landing<V>>>,
+}
+
+impl<V> Default for SingleFlight<V> {
+    fn default() -> Self {
+        SingleFlight {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// A request's part in the fetch for its key.
+pub enum Flight<'a, V> {
+    // Nothing identical is in flight, so this request fetches
+    Leader(Leader<'a, V>),
+    // An identical fetch is in flight; `wait` for its result
+    Follower(Landing<V>),
+}
+
+/// The request doing the fetch for a key. The key is freed when it's dropped,
+/// so a leader that never lands a result leaves its followers to fetch for
+/// themselves.
+pub struct Leader<'a, V> {
+    flights: &'a SingleFlight<V>,
+    key: String,
+    sender: watch::Sender<Option<Arc<V>>>,
+}
+
+impl<V> SingleFlight<V> {
+    /// Joins the fetch in flight for `key`, or starts one.
+    pub fn join(&self, key: String) -> Flight<'_, V> {
+        let mut in_flight = self.in_flight.lock().expect("single flight lock poisoned");
+        if let Some(landing) = in_flight.get(&key) {
+            return Flight::Follower(landing.clone());
+        }
+        let (sender, landing) = watch::channel(None);
+        in_flight.insert(key.clone(), landing);
+        Flight::Leader(Leader {
+            flights: self,
+            key,
+            sender,
+        })
+    }
+}
+
+impl<V> Leader<'_, V> {
+    /// Hands the result to every follower.
+    pub fn land(self, value: V) {
+        self.sender.send_replace(Some(Arc::new(value)));
+    }
+}
+
+impl<V> Drop for Leader<'_, V> {
+    fn drop(&mut self) {
+        let mut in_flight = self.flights.in_flight.lock().expect("single flight lock poisoned");
+        in_flight.remove(&self.key);
+    }
+}
+
+/// Waits for the leader's result, or `None` if it gave up without one.
+pub async fn wait<V>(mut landing: Landing<V>) -> Option<Arc<V>> {
+    let landed = landing.wait_for(Option::is_some).await.ok()?;
+    landed.clone()
+}
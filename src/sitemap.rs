@@ -0,0 +1,139 @@
+// sitemap.rs
+//
+// Sitemaps following the sitemaps.org protocol: a <urlset> lists page URLs,
+// a <sitemapindex> lists further sitemaps. Either may be served gzipped
+// without a Content-Encoding, as .xml.gz files usually are.
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use std::io::Read;
+
+// The two bytes every gzip stream starts with
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A page listed in a sitemap.
+#[derive(Serialize)]
+pub struct SitemapUrl {
+    pub loc: String,
+    // W3C datetime of the last change, as the sitemap gives it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lastmod: Option<String>,
+}
+
+/// The contents of a parsed sitemap file.
+pub enum Sitemap {
+    // A <urlset> and its pages
+    Urls(Vec<SitemapUrl>),
+    // A <sitemapindex> and the sitemaps it points to
+    Index(Vec<String>),
+}
+
+impl Sitemap {
+    /// Parses a sitemap document. Entries without a <loc> are skipped;
+    /// any root other than <urlset> or <sitemapindex> is an error.
+    pub fn parse(xml: &str) -> Result<Self, String> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| e.to_string())?;
+        let root = document.root_element();
+        // Namespaces vary between generators, so only local names are compared
+        let children = |name: &'static str| {
+            root.children()
+                .filter(move |node| node.is_element() && node.tag_name().name() == name)
+        };
+        let field = |node: roxmltree::Node<'_, '_>, name: &str| {
+            node.children()
+                .find(|child| child.is_element() && child.tag_name().name() == name)
+                .and_then(|child| child.text())
+                .map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty())
+        };
+
+        match root.tag_name().name() {
+            "urlset" => Ok(Sitemap::Urls(
+                children("url")
+                    .filter_map(|url| {
+                        Some(SitemapUrl {
+                            loc: field(url, "loc")?,
+                            lastmod: field(url, "lastmod"),
+                        })
+                    })
+                    .collect(),
+            )),
+            "sitemapindex" => Ok(Sitemap::Index(
+                children("sitemap").filter_map(|sitemap| field(sitemap, "loc")).collect(),
+            )),
+            other => Err(format!("expected <urlset> or <sitemapindex>, got <{}>", other)),
+        }
+    }
+}
+
+/// Decompresses a gzipped body, giving up past `limit` bytes so a small
+/// archive can't expand without bound. Bodies that aren't gzip are returned as-is.
+pub fn gunzip(body: Vec<u8>, limit: usize) -> Result<Vec<u8>, String> {
+    if !body.starts_with(&GZIP_MAGIC) {
+        return Ok(body);
+    }
+    let mut decompressed = Vec::new();
+    GzDecoder::new(body.as_slice())
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| format!("invalid gzip data ({})", e))?;
+    if decompressed.len() > limit {
+        return Err(format!("decompresses to more than {} bytes", limit));
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn parse_reads_a_urlset() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <url><loc> https://example.com/a </loc><lastmod>2024-01-02</lastmod></url>
+              <url><loc>https://example.com/b</loc></url>
+              <url><lastmod>2024-01-02</lastmod></url>
+            </urlset>"#;
+        let Ok(Sitemap::Urls(urls)) = Sitemap::parse(xml) else {
+            panic!("expected a urlset");
+        };
+        let urls: Vec<(&str, Option<&str>)> =
+            urls.iter().map(|url| (url.loc.as_str(), url.lastmod.as_deref())).collect();
+        assert_eq!(urls, [("https://example.com/a", Some("2024-01-02")), ("https://example.com/b", None)]);
+    }
+
+    #[test]
+    fn parse_reads_a_sitemap_index() {
+        let xml = "<sitemapindex><sitemap><loc>https://example.com/1.xml.gz</loc></sitemap>\
+                   <sitemap><loc></loc></sitemap></sitemapindex>";
+        let Ok(Sitemap::Index(sitemaps)) = Sitemap::parse(xml) else {
+            panic!("expected a sitemap index");
+        };
+        assert_eq!(sitemaps, ["https://example.com/1.xml.gz"]);
+    }
+
+    #[test]
+    fn parse_refuses_other_documents() {
+        assert!(Sitemap::parse("<html><body/></html>").is_err_and(|e| e.contains("<html>")));
+        assert!(Sitemap::parse("not xml").is_err());
+    }
+
+    #[test]
+    fn gunzip_decompresses_within_the_limit() {
+        let xml = b"<urlset></urlset>".to_vec();
+        assert_eq!(gunzip(gzip(&xml), 1024).unwrap(), xml);
+        // Plain bodies pass through untouched
+        assert_eq!(gunzip(xml.clone(), 1024).unwrap(), xml);
+        assert!(gunzip(gzip(&[b' '; 4096]), 1024).is_err());
+        assert!(gunzip(vec![0x1f, 0x8b, 0, 0], 1024).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+// sitemap_api.rs
+//
+// POST /sitemap: a sitemap expanded into its page URLs, following sitemap
+// indexes a few levels down and reporting the files that couldn't be read.
+use crate::{
+    check_robots, error_body, fetch, normalize_url, request_id, select_client, strip_headers, with_hard_timeout,
+    with_request_id, ClientOptions, FetchOptions, ProxyFields, ScrapeError, SelectedClient,
+};
+use crate::batch_api::ScrapeResult;
+use crate::circuit_breaker::CircuitBreakers;
+use crate::config::Config;
+use crate::politeness::HostThrottle;
+use crate::proxy_pool::ProxyPool;
+use crate::robots::RobotsCache;
+use crate::sitemap::{self, Sitemap, SitemapUrl};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
+use futures::future;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::Semaphore;
+use tracing::{field, info, info_span, Instrument, Span};
+
+// Levels of sitemap indexes followed below the requested sitemap
+const MAX_SITEMAP_DEPTH: usize = 3;
+// Most sitemap files fetched for one request, the requested one included
+const MAX_SITEMAPS: usize = 200;
+// Most page URLs returned for one request; the rest are dropped
+const MAX_SITEMAP_URLS: usize = 100_000;
+// Largest sitemap read, before and after decompression, as the protocol allows
+const MAX_SITEMAP_BYTES: usize = 50 * 1024 * 1024;
+
+// Define the structure for the incoming sitemap POST request
+#[derive(Deserialize)]
+pub struct SitemapRequest {
+    // The sitemap or sitemap index to expand
+    url: String,
+    // Optional proxy and timeouts, the timeouts applying to each sitemap file
+    #[serde(flatten)]
+    proxy_fields: ProxyFields,
+    // Optional robots.txt check, as in `ScrapeRequest`, applied to each sitemap file
+    respect_robots: Option<bool>,
+}
+
+// Define the structure for the outgoing sitemap JSON response
+#[derive(Serialize)]
+struct SitemapResponse {
+    // Every page URL listed, across nested sitemaps, in the order found
+    urls: Vec<SitemapUrl>,
+    // Number of sitemap files fetched, the requested one included
+    sitemaps: usize,
+    // Nested sitemaps that couldn't be fetched or parsed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed: Vec<ScrapeResult>,
+    // Set when a limit on depth, sitemaps or URLs left some of them out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncated: Option<bool>,
+}
+
+/// Handles the POST request to expand a sitemap into its page URLs.
+///
+/// Sitemap indexes are followed `MAX_SITEMAP_DEPTH` levels deep, one level at
+/// a time with each fetch holding a permit from the shared batch semaphore,
+/// and every sitemap is fetched at most once. Gzipped sitemaps are
+/// decompressed whatever their Content-Type. A failure on the requested
+/// sitemap fails the request; one on a nested sitemap is reported in `failed`.
+pub async fn sitemap_handler(
+    http_req: HttpRequest,
+    req: web::Json<SitemapRequest>,
+    config: web::Data<Config>,
+    base_client: web::Data<Client>,
+    proxy_pool: web::Data<ProxyPool>,
+    semaphore: web::Data<Semaphore>,
+    robots_cache: web::Data<RobotsCache>,
+    throttle: web::Data<HostThrottle>,
+    breakers: web::Data<CircuitBreakers>,
+) -> impl Responder {
+    let request_id = request_id(&http_req);
+    let span = info_span!("sitemap", request_id = %request_id, url = %req.url, proxy = field::Empty);
+
+    let prepared: Result<_, ScrapeError> = span.in_scope(|| {
+        let root = normalize_url(&config, &req.url)?;
+        let client_options = ClientOptions::from_proxy_fields(&req.proxy_fields)?;
+        let client = select_client(&config, &base_client, &proxy_pool, &breakers, &client_options)?;
+        Ok((root, client))
+    });
+    let (root, client) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => return with_request_id(HttpResponse::build(e.status_code()).json(error_body(&e)), &request_id),
+    };
+
+    // Kept as bytes, since a gzipped sitemap is rarely labelled as such
+    let options = FetchOptions {
+        max_bytes: config.max_response_bytes(Some(MAX_SITEMAP_BYTES)),
+        force_binary: true,
+        allowed_content_types: Vec::new(),
+        ..FetchOptions::new(&config)
+    };
+    let respect_robots = req.respect_robots.unwrap_or(config.respect_robots);
+    let mut seen = HashSet::from([root.clone()]);
+    let mut level = vec![root];
+    let mut urls = Vec::new();
+    let mut failed = Vec::new();
+    let mut truncated = false;
+    for depth in 0..=MAX_SITEMAP_DEPTH {
+        if level.is_empty() {
+            break;
+        }
+        let results = future::join_all(level.iter().map(|url| {
+            let url_span = info_span!(parent: &span, "scrape", url = %url, depth, status = field::Empty);
+            async {
+                let _permit = semaphore.acquire().await.expect("semaphore is never closed");
+                fetch_sitemap(&config, &throttle, &client, &robots_cache, url, &options, respect_robots).await
+            }
+            .instrument(url_span)
+        }))
+        .await;
+
+        let mut next = Vec::new();
+        for (url, result) in level.into_iter().zip(results) {
+            match result {
+                Ok(Sitemap::Urls(found)) => {
+                    let room = MAX_SITEMAP_URLS - urls.len();
+                    truncated |= found.len() > room;
+                    urls.extend(found.into_iter().take(room));
+                }
+                Ok(Sitemap::Index(sitemaps)) => {
+                    for sitemap in sitemaps {
+                        if depth == MAX_SITEMAP_DEPTH || seen.len() >= MAX_SITEMAPS {
+                            truncated = true;
+                            break;
+                        }
+                        match normalize_url(&config, &sitemap) {
+                            Ok(sitemap) => {
+                                if seen.insert(sitemap.clone()) {
+                                    next.push(sitemap);
+                                }
+                            }
+                            Err(e) => failed.push(failed_sitemap(sitemap, e)),
+                        }
+                    }
+                }
+                Err(e) if depth == 0 => {
+                    let response = HttpResponse::build(e.status_code()).json(strip_headers(&config, error_body(&e)));
+                    return with_request_id(response, &request_id);
+                }
+                Err(e) => failed.push(failed_sitemap(url, e)),
+            }
+        }
+        level = next;
+    }
+
+    span.in_scope(|| info!(urls = urls.len(), sitemaps = seen.len(), truncated, "Sitemap expanded"));
+    let response = SitemapResponse {
+        urls,
+        sitemaps: seen.len(),
+        failed,
+        truncated: truncated.then_some(true),
+    };
+    with_request_id(HttpResponse::Ok().json(response), &request_id)
+}
+
+/// Fetches, decompresses and parses one sitemap file.
+async fn fetch_sitemap(
+    config: &Config,
+    throttle: &HostThrottle,
+    client: &SelectedClient,
+    robots_cache: &RobotsCache,
+    url: &str,
+    options: &FetchOptions,
+    respect_robots: bool,
+) -> Result<Sitemap, ScrapeError> {
+    let operation = async {
+        if respect_robots {
+            check_robots(config, throttle, robots_cache, client, url, options).await?;
+        }
+        fetch(config, throttle, client, url, options).await.result
+    };
+    let result = with_hard_timeout(config, operation).await;
+    let status = result.as_ref().map_or_else(|e| e.status_code(), |_| StatusCode::OK);
+    Span::current().record("status", status.as_u16());
+
+    let body = result?.binary.unwrap_or_default();
+    let xml = sitemap::gunzip(body, MAX_SITEMAP_BYTES).map_err(ScrapeError::InvalidSitemap)?;
+    Sitemap::parse(&String::from_utf8_lossy(&xml)).map_err(ScrapeError::InvalidSitemap)
+}
+
+/// The entry of `failed` for a nested sitemap.
+fn failed_sitemap(url: String, e: ScrapeError) -> ScrapeResult {
+    ScrapeResult {
+        url,
+        status: e.response_meta().map(|meta| meta.status.as_u16()),
+        content: None,
+        content_base64: None,
+        error: Some(e.to_string()),
+        error_code: Some(e.code()),
+    }
+}
@@ -0,0 +1,315 @@
+// ssrf.rs
+//
+// Protection against server-side request forgery: callers must not be able to
+// use the scraper to reach loopback, private or link-local addresses (such as
+// the 169.254.169.254 cloud metadata endpoint), explicitly blocked hosts or,
+// when an allowlist is configured, any host not on it.
+//
+// Hostnames are checked *after* DNS resolution, inside the resolver reqwest
+// connects with, so the addresses that were vetted are the ones actually
+// dialled and a DNS-rebinding answer can't slip through. IP-literal URLs never
+// reach the resolver, so callers must check them with `check_url` up front and
+// on every redirect hop.
+use hickory_resolver::TokioAsyncResolver;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::Url;
+use std::env;
+use std::error::Error as StdError;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+/// Why a target was refused.
+#[derive(Debug)]
+pub struct Blocked(String);
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl StdError for Blocked {}
+
+/// Which targets may be scraped, read from `ALLOW_PRIVATE_IPS`, `BLOCKED_HOSTS`
+/// and `ALLOWED_DOMAINS`.
+pub struct SsrfGuard {
+    // Skip the private/loopback/link-local address check entirely
+    allow_private_ips: bool,
+    // Lowercased hostnames that are refused, along with their subdomains
+    blocked_hosts: Vec<String>,
+    // Lowercased hosts that may be scraped, `*.example.com` standing for any
+    // subdomain of example.com; every host may be when empty
+    allowed_domains: Vec<String>,
+}
+
+impl SsrfGuard {
+    pub fn from_env() -> Self {
+        let allow_private_ips = env::var("ALLOW_PRIVATE_IPS")
+            .map(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        SsrfGuard {
+            allow_private_ips,
+            blocked_hosts: host_list("BLOCKED_HOSTS"),
+            allowed_domains: host_list("ALLOWED_DOMAINS"),
+        }
+    }
+
+    /// Whether only the hosts in `ALLOWED_DOMAINS` may be scraped.
+    pub fn has_allowlist(&self) -> bool {
+        !self.allowed_domains.is_empty()
+    }
+
+    /// Checks the host of a URL against the blocklist and, for IP literals,
+    /// against the disallowed address ranges.
+    pub fn check_url(&self, url: &Url) -> Result<(), Blocked> {
+        if let Some(host) = url.host_str() {
+            self.check_allowed(host)?;
+        }
+        match url.host() {
+            Some(url::Host::Domain(domain)) => self.check_host(domain),
+            Some(url::Host::Ipv4(ip)) => self.check_ip(IpAddr::V4(ip)),
+            Some(url::Host::Ipv6(ip)) => self.check_ip(IpAddr::V6(ip)),
+            None => Ok(()),
+        }
+    }
+
+    /// Refuses hosts listed in `BLOCKED_HOSTS` and their subdomains.
+    fn check_host(&self, host: &str) -> Result<(), Blocked> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let is_blocked = self.blocked_hosts.iter().any(|blocked| {
+            host == *blocked
+                || host
+                    .strip_suffix(blocked.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        });
+        if is_blocked {
+            return Err(Blocked(format!("Host {} is blocked", host)));
+        }
+        Ok(())
+    }
+
+    /// Refuses hosts missing from `ALLOWED_DOMAINS`, when it's set. An IP
+    /// address is only allowed when listed as such.
+    fn check_allowed(&self, host: &str) -> Result<(), Blocked> {
+        if self.allowed_domains.is_empty() {
+            return Ok(());
+        }
+        let host = host.trim_start_matches('[').trim_end_matches(']').trim_end_matches('.').to_ascii_lowercase();
+        let is_allowed = self.allowed_domains.iter().any(|allowed| match allowed.strip_prefix("*.") {
+            Some(parent) => host.strip_suffix(parent).is_some_and(|prefix| prefix.ends_with('.')),
+            None => host == *allowed,
+        });
+        if !is_allowed {
+            return Err(Blocked(format!("Host {} is not in ALLOWED_DOMAINS", host)));
+        }
+        Ok(())
+    }
+
+    /// Refuses addresses in loopback, private or link-local ranges unless
+    /// `ALLOW_PRIVATE_IPS` is set.
+    fn check_ip(&self, ip: IpAddr) -> Result<(), Blocked> {
+        if !self.allow_private_ips && is_disallowed_ip(ip) {
+            return Err(Blocked(format!(
+                "Address {} is in a private, loopback or link-local range",
+                ip
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Reads a comma-separated list of hostnames, lowercased and without trailing dots.
+fn host_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|host| host.trim().trim_end_matches('.').to_ascii_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether an address is one the scraper must not connect to by default.
+pub fn is_disallowed_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_disallowed_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_disallowed_ipv4(mapped),
+            None => is_disallowed_ipv6(ip),
+        },
+    }
+}
+
+fn is_disallowed_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // 0.0.0.0/8 "this network"
+        || a == 0
+        // 100.64.0.0/10 carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+}
+
+fn is_disallowed_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // fc00::/7 unique local
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10 link-local
+        || (first & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+impl SsrfGuard {
+    /// A guard refusing nothing, for tests fetching from fixtures on loopback.
+    pub fn allowing_private_ips() -> Self {
+        SsrfGuard {
+            allow_private_ips: true,
+            blocked_hosts: Vec::new(),
+            allowed_domains: Vec::new(),
+        }
+    }
+}
+
+/// DNS resolver that drops disallowed addresses from lookups and fails when
+/// none are left. Lookups go to `upstream` when given, otherwise to the system
+/// resolver.
+pub struct GuardedResolver {
+    guard: Arc<SsrfGuard>,
+    upstream: Option<Arc<TokioAsyncResolver>>,
+}
+
+impl GuardedResolver {
+    pub fn new(guard: Arc<SsrfGuard>, upstream: Option<Arc<TokioAsyncResolver>>) -> Self {
+        GuardedResolver { guard, upstream }
+    }
+}
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let guard = self.guard.clone();
+        let upstream = self.upstream.clone();
+        Box::pin(async move {
+            let host = name.as_str();
+            guard.check_host(host)?;
+
+            // The port is replaced by the connector, so any value works here
+            let resolved: Vec<SocketAddr> = match &upstream {
+                Some(resolver) => resolver.lookup_ip(host).await?.iter().map(|ip| SocketAddr::new(ip, 0)).collect(),
+                None => tokio::net::lookup_host((host, 0)).await?.collect(),
+            };
+            let allowed: Vec<SocketAddr> = resolved
+                .iter()
+                .copied()
+                .filter(|addr| guard.check_ip(addr.ip()).is_ok())
+                .collect();
+
+            if allowed.is_empty() {
+                if let Some(addr) = resolved.first() {
+                    return Err(Box::new(Blocked(format!(
+                        "Host {} resolves to {}, which is in a private, loopback or link-local range",
+                        host,
+                        addr.ip()
+                    ))) as Box<dyn StdError + Send + Sync>);
+                }
+            }
+            Ok(Box::new(allowed.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Looks for a `Blocked` error anywhere in the source chain of a reqwest error.
+pub fn find_blocked(error: &reqwest::Error) -> Option<&Blocked> {
+    let mut source = error.source();
+    while let Some(err) = source {
+        if let Some(blocked) = err.downcast_ref::<Blocked>() {
+            return Some(blocked);
+        }
+        source = err.source();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(blocked_hosts: &[&str], allowed_domains: &[&str]) -> SsrfGuard {
+        SsrfGuard {
+            allow_private_ips: false,
+            blocked_hosts: blocked_hosts.iter().map(|host| host.to_string()).collect(),
+            allowed_domains: allowed_domains.iter().map(|host| host.to_string()).collect(),
+        }
+    }
+
+    fn check(guard: &SsrfGuard, url: &str) -> Result<(), Blocked> {
+        guard.check_url(&Url::parse(url).expect("test URL parses"))
+    }
+
+    #[test]
+    fn internal_addresses_are_disallowed() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "169.254.169.254",
+            "172.16.0.1",
+            "192.168.1.1",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::ffff:10.0.0.1",
+            "fd00::1",
+            "fe80::1",
+        ] {
+            assert!(is_disallowed_ip(ip.parse().unwrap()), "{} should be disallowed", ip);
+        }
+        for ip in ["93.184.216.34", "100.128.0.1", "2606:4700::1111"] {
+            assert!(!is_disallowed_ip(ip.parse().unwrap()), "{} should be allowed", ip);
+        }
+    }
+
+    #[test]
+    fn ip_literals_are_checked_unless_private_ips_are_allowed() {
+        let refusing = guard(&[], &[]);
+        assert!(check(&refusing, "http://127.0.0.1:8080/").is_err());
+        assert!(check(&refusing, "http://10.0.0.1/").is_err());
+        assert!(check(&refusing, "http://169.254.169.254/latest/meta-data/").is_err());
+        assert!(check(&refusing, "http://[::1]/").is_err());
+        assert!(check(&refusing, "http://93.184.216.34/").is_ok());
+        assert!(check(&SsrfGuard::allowing_private_ips(), "http://169.254.169.254/").is_ok());
+    }
+
+    #[test]
+    fn blocked_hosts_cover_their_subdomains() {
+        let guard = guard(&["internal.example"], &[]);
+        assert!(check(&guard, "http://internal.example/").is_err());
+        assert!(check(&guard, "http://api.INTERNAL.example./").is_err());
+        assert!(check(&guard, "http://notinternal.example/").is_ok());
+    }
+
+    #[test]
+    fn allowlist_admits_listed_hosts_only() {
+        let guard = guard(&[], &["example.com", "*.example.org"]);
+        assert!(check(&guard, "http://example.com/").is_ok());
+        assert!(check(&guard, "http://www.example.com/").is_err());
+        assert!(check(&guard, "http://a.b.example.org/").is_ok());
+        assert!(check(&guard, "http://example.org/").is_err());
+        assert!(check(&guard, "http://evilexample.org/").is_err());
+        assert!(check(&guard, "http://93.184.216.34/").is_err());
+    }
+
+    #[tokio::test]
+    async fn resolved_loopback_is_refused() {
+        let resolver = GuardedResolver::new(Arc::new(guard(&[], &[])), None);
+        let error = resolver.resolve("localhost".parse().unwrap()).await.err().expect("localhost is refused");
+        assert!(error.downcast_ref::<Blocked>().is_some());
+    }
+}
@@ -0,0 +1,97 @@
+// tor_control.rs
+//
+// Just enough of Tor's control protocol to ask for a new circuit: connect to
+// TOR_CONTROL_ADDR, authenticate with TOR_CONTROL_PASSWORD (or without one, for
+// a control port left open) and send SIGNAL NEWNYM. Tor then builds new
+// circuits for new connections, so the next one through its SOCKS port
+// usually leaves from another exit. Tor rate-limits NEWNYM to one every few
+// seconds, answering 250 OK all the same, so a circuit requested too soon
+// after the last one may not change.
+use std::fmt;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+// How long the whole exchange with the control port may take
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why Tor couldn't be asked for a new circuit.
+#[derive(Debug)]
+pub enum TorControlError {
+    // Nothing answered at the control address
+    Connect(io::Error),
+    // The connection broke, or Tor closed it mid-reply
+    Io(io::Error),
+    // Tor refused our password, or wants one we don't have; holds its reply
+    Authentication(String),
+    // Tor refused the signal; holds its reply
+    Rejected(String),
+    // The control port didn't answer within CONTROL_TIMEOUT
+    TimedOut,
+}
+
+impl fmt::Display for TorControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TorControlError::Connect(e) => write!(f, "can't connect to the control port ({})", e),
+            TorControlError::Io(e) => write!(f, "control connection failed ({})", e),
+            TorControlError::Authentication(reply) => write!(f, "authentication failed ({})", reply),
+            TorControlError::Rejected(reply) => write!(f, "NEWNYM refused ({})", reply),
+            TorControlError::TimedOut => {
+                write!(f, "no answer within {} seconds", CONTROL_TIMEOUT.as_secs())
+            }
+        }
+    }
+}
+
+/// Asks the Tor instance whose control port listens at `addr` for a new
+/// circuit, authenticating with `password` if given.
+pub async fn new_circuit(addr: &str, password: Option<&str>) -> Result<(), TorControlError> {
+    tokio::time::timeout(CONTROL_TIMEOUT, signal_newnym(addr, password))
+        .await
+        .unwrap_or(Err(TorControlError::TimedOut))
+}
+
+async fn signal_newnym(addr: &str, password: Option<&str>) -> Result<(), TorControlError> {
+    let stream = TcpStream::connect(addr).await.map_err(TorControlError::Connect)?;
+    let mut control = BufReader::new(stream);
+
+    // A hex password needs no quoting, whatever characters it holds
+    let authenticate = match password {
+        Some(password) => format!("AUTHENTICATE {}\r\n", hex(password.as_bytes())),
+        None => "AUTHENTICATE\r\n".to_string(),
+    };
+    let reply = command(&mut control, &authenticate).await?;
+    if !reply.starts_with("250") {
+        return Err(TorControlError::Authentication(reply));
+    }
+    let reply = command(&mut control, "SIGNAL NEWNYM\r\n").await?;
+    if !reply.starts_with("250") {
+        return Err(TorControlError::Rejected(reply));
+    }
+    // Tor closes the connection itself; failing to say goodbye changes nothing
+    let _ = control.get_mut().write_all(b"QUIT\r\n").await;
+    Ok(())
+}
+
+/// Sends a command and reads Tor's reply, giving its last line: a reply spans
+/// `250-` lines up to a final one with a space after the status, like `250 OK`.
+async fn command(control: &mut BufReader<TcpStream>, line: &str) -> Result<String, TorControlError> {
+    control.get_mut().write_all(line.as_bytes()).await.map_err(TorControlError::Io)?;
+    loop {
+        let mut reply = String::new();
+        let read = control.read_line(&mut reply).await.map_err(TorControlError::Io)?;
+        if read == 0 {
+            return Err(TorControlError::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let reply = reply.trim_end();
+        if reply.as_bytes().get(3).is_none_or(|&separator| separator == b' ') {
+            return Ok(reply.to_string());
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,27 @@
+// version_api.rs
+//
+// GET /version: the crate version, commit and build time of the running binary.
+use actix_web::{HttpResponse, Responder};
+use serde::Serialize;
+use std::time::Duration;
+
+// Body returned by /version, identifying the running build
+#[derive(Serialize)]
+struct VersionResponse {
+    version: &'static str,
+    // Commit the binary was built from, "unknown" when the build couldn't tell
+    git_commit: &'static str,
+    // When the binary was built, as an HTTP date
+    built_at: String,
+}
+
+/// Reports the crate version, commit and build time recorded by build.rs,
+/// to confirm which build a deployment runs.
+pub async fn version_handler() -> impl Responder {
+    let built_at: u64 = env!("BUILD_TIMESTAMP").parse().expect("build.rs records a number of seconds");
+    HttpResponse::Ok().json(VersionResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("BUILD_GIT_COMMIT"),
+        built_at: httpdate::fmt_http_date(std::time::UNIX_EPOCH + Duration::from_secs(built_at)),
+    })
+}